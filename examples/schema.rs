@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use wefund17::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use wefund17::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, MigrateMsg, AllProjectResponse};
 use wefund17::state::{Milestone};
 
 fn main() {
@@ -15,5 +15,7 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(Milestone), &out_dir);
+    export_schema(&schema_for!(AllProjectResponse), &out_dir);
 }
@@ -1,28 +1,42 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Addr, to_binary, DepsMut, Env, MessageInfo, Response, StdResult,
-    Uint128, CosmosMsg, BankMsg, QueryRequest, BankQuery, WasmMsg,
-    Coin, AllBalanceResponse
+    Addr, to_binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
+    Uint128, CosmosMsg, BankMsg, WasmMsg,
+    Coin, Reply, SubMsg, Event
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw_storage_plus::{U128Key};
-use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, BalanceResponse as Cw20BalanceResponse, TokenInfoResponse};
+use cosmwasm_bignumber::Decimal256;
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg};
-use crate::state::{Config, CONFIG, PROJECTSTATES, ProjectState, BackerState, VestingParameter,
-        PROJECT_SEQ, COMMUNITY, Milestone, Vote, save_projectstate, TeamMember, ProjectStatus};
-
-use crate::market::{ExecuteMsg as AnchorMarket, Cw20HookMsg,
-    QueryMsg as AnchorQuery, EpochStateResponse};                    
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg};
+use crate::legacy::{migrate_config, migrate_projects, seed_contract_balances, MIGRATION_CURSOR};
+use crate::state::{Config, CONFIG, OwnershipProposal, ConfigChange, CONFIG_CHANGES,
+        CONFIG_CHANGE_SEQ, DEFAULT_TIMELOCK_PERIOD, LEDGER, Ledger, PauseOperation, PauseState, PAUSE_STATE, projectstates, ProjectState, BackerState, VestingParameter,
+        PROJECT_SEQ, COMMUNITY, Milestone, Vote, save_projectstate, TeamMember, ProjectStatus,
+        VOTE_DELEGATES, VoteOption, MILESTONE_REJECTED, DEFAULT_MILESTONE_QUORUM,
+        DEFAULT_MILESTONE_THRESHOLD, DEFAULT_MILESTONE_VOTEPERIOD, RejectionPolicy,
+        REFUND_POOLS, REFUNDS_CLAIMED, Role, ROLES, load_roles,
+        MILESTONE_VOTING, MILESTONE_APPROVED, MILESTONE_RELEASED,
+        BACKERS, COMMUNITY_BACKERS, MILESTONES, MILESTONE_VOTES, milestone_key, add_backer,
+        remove_projectstate,
+        PendingDeposit, PENDING_DEPOSIT, add_backer_aust, PendingRedemption, PENDING_REDEMPTION,
+        RedemptionPayout, YieldSplit, YIELD_CLAIMED, FeeSchedule, FeeRecipient, FEE_REMAINDER, CONTRACT_FEES, CONTRACT_YIELD,
+        ACCRUED_FEES, MIN_FEE_PAYOUT, add_referral, TOKENS_SOLD};
+
+use crate::strategy::{YieldStrategy, ust_to_shares, ust_to_shares_ceil, shares_to_ust};
 
 use crate::vesting::{ExecuteMsg as VestingMsg, VestingParameter as VestingParam};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "WEFUND";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
-const UST: u128 = 1000000; //ust unit
+pub const UST: u128 = 1000000; //ust unit
+pub const UST_DENOM: &str = "uusd"; //the only coin backings are taken in
+pub const DEPOSIT_REPLY_ID: u64 = 1; //strategy deposit, credits the minted shares
+pub const REDEEM_REPLY_ID: u64 = 2; //strategy redeem, pays out the ust received
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -33,39 +47,50 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    let owner = msg
-        .admin
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(info.sender.clone());
+    //-------given addresses must be valid, only missing ones fall back to the defaults-------
+    let owner = validate_opt(deps.as_ref(), msg.admin)?
+        .unwrap_or_else(|| info.sender.clone());
 
-    let wefund = msg
-        .wefund
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(info.sender.clone());
+    let wefund = validate_opt(deps.as_ref(), msg.wefund)?
+        .unwrap_or_else(|| info.sender.clone());
 
-    let anchor_market = msg
-        .anchor_market
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(Addr::unchecked(
+    let anchor_market = validate_opt(deps.as_ref(), msg.anchor_market)?
+        .unwrap_or_else(|| Addr::unchecked(
             // String::from("terra1sepfj7s0aeg5967uxnfk4thzlerrsktkpelm5s")));//main net
             String::from("terra15dwd5mj8v59wpj0wvt233mf5efdff808c5tkal")));//test net
-    let aust_token = msg
-        .aust_token
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(Addr::unchecked(
+    let aust_token = validate_opt(deps.as_ref(), msg.aust_token)?
+        .unwrap_or_else(|| Addr::unchecked(
             // String::from("terra1hzh9vpxhsk8253se0vv5jj6etdvxu3nv8z07zu")));//main net
             String::from("terra1ajt556dpzvjwl0kl5tzku3fc3p3knkg9mkv8jl")));//test net
 
-    let vesting_contract = msg
-        .vesting_contract
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(Addr::unchecked("".to_string()));
+    let vesting_contract = validate_opt(deps.as_ref(), msg.vesting_contract)?
+        .unwrap_or_else(|| Addr::unchecked("".to_string()));
+
+    let community_manager = validate_opt(deps.as_ref(), msg.community_manager)?
+        .unwrap_or_else(|| owner.clone());
+
+    let timelock_period = msg.timelock_period.unwrap_or(DEFAULT_TIMELOCK_PERIOD);
+    let yield_split = msg.yield_split.unwrap_or_default();
+    check_yield_split(&yield_split)?;
+    let yield_strategy = msg.yield_strategy.unwrap_or_default();
+    validate_strategy(deps.as_ref(), &yield_strategy)?;
+    let fee_schedule = msg.fee_schedule.unwrap_or_default();
+    check_fee_schedule(deps.as_ref(), &fee_schedule)?;
 
     let config = Config {
-        owner, wefund, anchor_market, aust_token, vesting_contract
+        owner, wefund, anchor_market, aust_token, vesting_contract, community_manager,
+        pending_owner: None,
+        timelock_period,
+        yield_split,
+        yield_strategy,
+        fee_schedule,
+        referral_share: Uint128::zero(), //set with SetConfig
     };
 
     CONFIG.save(deps.storage, &config)?;
+    CONFIG_CHANGE_SEQ.save(deps.storage, &Uint128::zero())?;
+    PAUSE_STATE.save(deps.storage, &PauseState::default())?;
+    LEDGER.save(deps.storage, &Ledger::default())?;
     PROJECT_SEQ.save(deps.storage, &Uint128::new(0))?;
     COMMUNITY.save(deps.storage, &Vec::new())?;
 
@@ -73,6 +98,165 @@ pub fn instantiate(
         .add_attribute("method", "instantiate"))
 }
 
+const MIGRATION_LIMIT: u32 = 30; //projects converted per call
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    //-------same contract, never to an older version-----------------
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate{ previous_contract: stored.contract });
+    }
+    if parse_version(&stored.version) > parse_version(CONTRACT_VERSION) {
+        return Err(ContractError::CannotDowngrade{
+            stored: stored.version, current: CONTRACT_VERSION.to_string()
+        });
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    //-------config at once, projects of the old layout in pages----------
+    if migrate_config(deps.storage)? {
+        seed_contract_balances(deps.branch(), &env)?;
+        MIGRATION_CURSOR.save(deps.storage, &Uint128::zero())?;
+    }
+    let limit = msg.limit.unwrap_or(MIGRATION_LIMIT) as usize;
+    let done = migrate_projects(deps.branch(), &env, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION)
+        .add_attribute("migration_done", done.to_string()))
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version.split('-').next().unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or_default())
+        .collect()
+}
+
+pub fn try_migrateprojects(deps: DepsMut, env: Env, info: MessageInfo, limit: Option<u32>)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    let limit = limit.unwrap_or(MIGRATION_LIMIT) as usize;
+    let done = migrate_projects(deps, &env, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate projects")
+        .add_attribute("migration_done", done.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        DEPOSIT_REPLY_ID => try_depositreply(deps, msg),
+        REDEEM_REPLY_ID => try_redeemreply(deps, _env),
+        id => Err(ContractError::UnknownReply{ id }),
+    }
+}
+
+//-----------pay out what the redeem actually brought in, the rest is a shortfall------
+pub fn try_redeemreply(deps: DepsMut, env: Env) -> Result<Response, ContractError>
+{
+    let pending: PendingRedemption = PENDING_REDEMPTION.load(deps.storage)?;
+    PENDING_REDEMPTION.remove(deps.storage);
+
+    let balance = deps.querier.query_balance(env.contract.address, UST_DENOM)?.amount;
+    let received = balance.checked_sub(pending.balance_before).unwrap_or_default();
+    let shortfall = pending.expected.checked_sub(received).unwrap_or_default();
+    if !shortfall.is_zero() {
+        projectstates().update(deps.storage, pending.project_id.u128().into(), |op| match op {
+            None => Err(ContractError::NotRegisteredProject {}),
+            Some(mut project) => {
+                project.redemption_shortfall += shortfall;
+                Ok(project)
+            }
+        })?;
+    }
+
+    let msgs = pay_out(deps.storage, pending.project_id, pending.payout, received)?;
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "redeem reply")
+        .add_attribute("project_id", pending.project_id)
+        .add_attribute("received", received)
+        .add_attribute("shortfall", shortfall))
+}
+
+//-----------redeem through a reply, or pay at once when the ust never left the contract-----
+fn redeem_and_pay(deps: DepsMut, env: &Env, x: &ProjectState, withdraw_amount: Uint128,
+    expected: Uint128, payout: RedemptionPayout) -> StdResult<Vec<SubMsg>>
+{
+    let config = CONFIG.load(deps.storage)?;
+    match x.yield_strategy.redeem_msg(&config, withdraw_amount)? {
+        Some(redeem) => {
+            let balance = deps.querier.query_balance(env.contract.address.clone(), UST_DENOM)?.amount;
+            PENDING_REDEMPTION.save(deps.storage, &PendingRedemption{
+                project_id: x.project_id,
+                payout,
+                expected,
+                balance_before: balance,
+            })?;
+            Ok(vec![SubMsg::reply_on_success(redeem, REDEEM_REPLY_ID)])
+        }
+        None => pay_out(deps.storage, x.project_id, payout, expected),
+    }
+}
+
+fn pay_out(storage: &mut dyn Storage, project_id: Uint128, payout: RedemptionPayout, amount: Uint128)
+    -> StdResult<Vec<SubMsg>>
+{
+    match payout {
+        RedemptionPayout::Send{ recipient } => {
+            if amount.is_zero() {
+                return Ok(vec![]);
+            }
+            Ok(vec![SubMsg::new(BankMsg::Send{
+                to_address: recipient.to_string(),
+                amount: vec![Coin::new(amount.u128(), UST_DENOM)],
+            })])
+        }
+        RedemptionPayout::RefundPool{} => {
+            //---------backers and community backers claim their share later-------------
+            REFUND_POOLS.save(storage, project_id.u128().into(), &amount)?;
+            Ok(vec![])
+        }
+    }
+}
+
+//-----------credit the shares minted by the deposit to the project and the backer--------
+pub fn try_depositreply(deps: DepsMut, msg: Reply) -> Result<Response, ContractError>
+{
+    let deposit: PendingDeposit = PENDING_DEPOSIT.load(deps.storage)?;
+    PENDING_DEPOSIT.remove(deps.storage);
+
+    let events = msg.result.into_result().map_err(StdError::generic_err)?.events;
+    let mint_amount = events.iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "mint_amount")
+        .and_then(|attr| attr.value.parse::<u128>().ok());
+
+    //--------no mint_amount reported, take the strategy's exchange rate---------
+    let aust_amount = match mint_amount {
+        Some(amount) => Uint128::new(amount),
+        None => {
+            let x = projectstates().load(deps.storage, deposit.project_id.u128().into())?;
+            ust_to_shares(deposit.ust_amount, query_exchange_rate(deps.as_ref(), &x)?)?
+        }
+    };
+
+    add_backer_aust(deps.storage, deposit.project_id, &deposit.wallet, deposit.community,
+        aust_amount)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit reply")
+        .add_attribute("project_id", deposit.project_id)
+        .add_attribute("aust_amount", aust_amount.to_string()))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -80,9 +264,42 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    //--------projects of the old layout don't load until the migration is done---------
+    if !matches!(msg, ExecuteMsg::MigrateProjects{ .. }) && MIGRATION_CURSOR.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::MigrationInProgress{});
+    }
+
     match msg {
-        ExecuteMsg::SetConfig{ admin, wefund, anchor_market, aust_token, vesting_contract } 
-            => try_setconfig(deps, _env, info, admin, wefund, anchor_market, aust_token, vesting_contract),
+        ExecuteMsg::SetConfig{ wefund, community_manager, yield_split, referral_share } 
+            => try_setconfig(deps, _env, info, wefund, community_manager, yield_split, referral_share),
+
+        ExecuteMsg::Pause{ operations }
+            => try_pause(deps, info, operations),
+
+        ExecuteMsg::Unpause{ operations }
+            => try_unpause(deps, info, operations),
+
+        ExecuteMsg::ProposeConfigChange{ anchor_market, aust_token, vesting_contract, timelock_period, yield_strategy }
+            => try_proposeconfigchange(deps, _env, info, anchor_market, aust_token, vesting_contract, timelock_period,
+                yield_strategy),
+
+        ExecuteMsg::ExecuteConfigChange{ id }
+            => try_executeconfigchange(deps, _env, info, id),
+
+        ExecuteMsg::CancelConfigChange{ id }
+            => try_cancelconfigchange(deps, info, id),
+
+        ExecuteMsg::MigrateProjects{ limit }
+            => try_migrateprojects(deps, _env, info, limit),
+
+        ExecuteMsg::ProposeNewOwner{ new_owner, expires_in }
+            => try_proposenewowner(deps, _env, info, new_owner, expires_in),
+
+        ExecuteMsg::AcceptOwnership{}
+            => try_acceptownership(deps, _env, info),
+
+        ExecuteMsg::CancelOwnershipProposal{}
+            => try_cancelownershipproposal(deps, info),
         ExecuteMsg::AddProject { 
             project_company,
             project_title,
@@ -99,7 +316,11 @@ pub fn execute(
             project_milestones,
             project_teammembers,
             vesting,
-            token_addr
+            token_addr,
+            milestone_quorum,
+            milestone_threshold,
+            milestone_voteperiod,
+            rejection_policy,
         } => 
             try_addproject(deps, _env, info, 
                 project_company,
@@ -117,57 +338,216 @@ pub fn execute(
                 project_milestones,
                 project_teammembers,
                 vesting,
-                token_addr
+                token_addr,
+                milestone_quorum,
+                milestone_threshold,
+                milestone_voteperiod,
+                rejection_policy,
             ),
 
-        ExecuteMsg::Back2Project { project_id, backer_wallet, fundraising_stage, token_amount, otherchain, otherchain_wallet} => 
-            try_back2project(deps, _env, info, project_id, backer_wallet, fundraising_stage, token_amount, otherchain, otherchain_wallet),
+        ExecuteMsg::Back2Project { project_id, backer_wallet, otherchain, otherchain_wallet, referrer} => 
+            try_back2project(deps, _env, info, project_id, backer_wallet, otherchain, otherchain_wallet, referrer),
 
         ExecuteMsg::CompleteProject{ project_id } =>
-            try_completeproject(deps, _env, project_id ),
+            try_completeproject(deps, _env, info, project_id ),
 
         ExecuteMsg::FailProject{ project_id } =>
-            try_failproject(deps, _env, project_id),
+            try_failproject(deps, _env, info, project_id),
         
         ExecuteMsg::RemoveProject{ project_id } =>
             try_removeproject(deps, info, project_id),
+
+        ExecuteMsg::SetProjectStrategy{ project_id, strategy } =>
+            try_setprojectstrategy(deps, info, project_id, strategy),
+
+        ExecuteMsg::SetFeeSchedule{ fee_schedule } =>
+            try_setfeeschedule(deps, info, fee_schedule),
+
+        ExecuteMsg::SetProjectFeeSchedule{ project_id, fee_schedule } =>
+            try_setprojectfeeschedule(deps, info, project_id, fee_schedule),
         
         ExecuteMsg::TransferAllCoins{wallet} =>
-            try_transferallcoins(deps, _env, info, wallet),
+            try_transferallcoins(deps, info, wallet),
 
         ExecuteMsg::AddCommunitymember{wallet} =>
-            try_addcommunitymember(deps, wallet),
+            try_addcommunitymember(deps, info, wallet),
 
         ExecuteMsg::RemoveCommunitymember{wallet} =>
-            try_removecommunitymember(deps, wallet),
+            try_removecommunitymember(deps, info, wallet),
 
         ExecuteMsg::WefundApprove{project_id} =>
             try_wefundapprove(deps, info, project_id),
 
         ExecuteMsg::SetFundraisingStage{project_id, stage} =>
-            try_setfundraisingstage(deps, project_id, stage),
+            try_setfundraisingstage(deps, info, project_id, stage),
         
-        ExecuteMsg::SetMilestoneVote{project_id, wallet, voted} =>
-            try_setmilestonevote(deps, _env, info, project_id, wallet, voted),
+        ExecuteMsg::SetMilestoneVote{project_id, wallet, vote} =>
+            try_setmilestonevote(deps, _env, info, project_id, wallet, vote),
+
+        ExecuteMsg::SetVoteDelegate{project_id, delegate} =>
+            try_setvotedelegate(deps, info, project_id, delegate),
+
+        ExecuteMsg::CloseMilestoneVote{project_id} =>
+            try_closemilestonevote(deps, _env, project_id),
+
+        ExecuteMsg::ResubmitMilestone{project_id} =>
+            try_resubmitmilestone(deps, _env, info, project_id),
 
         ExecuteMsg::ReleaseMilestone{project_id} =>
-            try_releasemilestone(deps, _env, project_id),
+            try_releasemilestone(deps, _env, info, project_id),
+
+        ExecuteMsg::ClaimRefund{project_id} =>
+            try_claimrefund(deps, info, project_id),
+
+        ExecuteMsg::ClaimYield{project_id} =>
+            try_claimyield(deps, _env, info, project_id),
+
+        ExecuteMsg::ClaimFees{} =>
+            try_claimfees(deps, info),
 
         ExecuteMsg::SetProjectStatus{project_id, status} =>
             try_setprojectstatus(deps, info, project_id, status),
+
+        ExecuteMsg::GrantRole{address, role} =>
+            try_grantrole(deps, info, address, role),
+
+        ExecuteMsg::RevokeRole{address, role} =>
+            try_revokerole(deps, info, address, role),
+
+        ExecuteMsg::RenounceRole{role} =>
+            try_renouncerole(deps, info, role),
     }
 }
+//-----------check the sender holds one of the roles---------------------
+pub fn check_roles(deps: Deps, sender: &Addr, roles: &[Role], project: Option<&ProjectState>)
+    -> Result<(), ContractError>
+{
+    let held = load_roles(deps.storage, sender)?;
+    for role in roles {
+        let allowed = match role {
+            Role::Creator => match project {
+                Some(x) => sender == &x.creator_wallet,
+                None => false,
+            },
+            _ => held.contains(role),
+        };
+        if allowed {
+            return Ok(());
+        }
+    }
+    Err(ContractError::Unauthorized{})
+}
+//-----------fail if the operation is paused---------------------
+pub fn check_paused(storage: &dyn Storage, operation: PauseOperation)
+    -> Result<(), ContractError>
+{
+    if PAUSE_STATE.load(storage)?.is_paused(&operation) {
+        return Err(ContractError::Paused{ operation });
+    }
+    Ok(())
+}
+fn set_paused(storage: &mut dyn Storage, operations: &[PauseOperation], paused: bool)
+    -> StdResult<PauseState>
+{
+    PAUSE_STATE.update(storage, |mut pause| -> StdResult<_> {
+        for op in operations {
+            *pause.flag(op) = paused;
+        }
+        Ok(pause)
+    })
+}
+pub fn try_pause(deps: DepsMut, info: MessageInfo, operations: Vec<PauseOperation>)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Guardian], None)?;
+    set_paused(deps.storage, &operations, true)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "pause")
+        .add_attribute("operations", format!("{:?}", operations)))
+}
+pub fn try_unpause(deps: DepsMut, info: MessageInfo, operations: Vec<PauseOperation>)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+    set_paused(deps.storage, &operations, false)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unpause")
+        .add_attribute("operations", format!("{:?}", operations)))
+}
+pub fn try_grantrole(deps: DepsMut, info: MessageInfo, address: String, role: Role)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    //-------owner moves with config, creator comes from project------
+    if role == Role::Owner || role == Role::Creator {
+        return Err(ContractError::NotGrantableRole{});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    ROLES.update(deps.storage, &address, |roles| -> StdResult<_> {
+        let mut roles = roles.unwrap_or_default();
+        if !roles.contains(&role) {
+            roles.push(role.clone());
+        }
+        Ok(roles)
+    })?;
+
+    Ok(Response::new()
+    .add_attribute("action", "grant role")
+    .add_attribute("address", address)
+    .add_attribute("role", format!("{:?}", role))
+    )
+}
+fn remove_role(deps: DepsMut, address: &Addr, role: &Role)
+    -> Result<(), ContractError>
+{
+    let mut roles = ROLES.may_load(deps.storage, address)?.unwrap_or_default();
+    if !roles.contains(role) {
+        return Err(ContractError::NotGrantedRole{});
+    }
+    roles.retain(|x| x != role);
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, address);
+    } else {
+        ROLES.save(deps.storage, address, &roles)?;
+    }
+    Ok(())
+}
+pub fn try_revokerole(deps: DepsMut, info: MessageInfo, address: String, role: Role)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    let address = deps.api.addr_validate(&address)?;
+    remove_role(deps, &address, &role)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "revoke role")
+    .add_attribute("address", address)
+    .add_attribute("role", format!("{:?}", role))
+    )
+}
+pub fn try_renouncerole(deps: DepsMut, info: MessageInfo, role: Role)
+    -> Result<Response, ContractError>
+{
+    remove_role(deps, &info.sender, &role)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "renounce role")
+    .add_attribute("address", info.sender)
+    .add_attribute("role", format!("{:?}", role))
+    )
+}
 pub fn try_setprojectstatus(deps: DepsMut, info: MessageInfo, project_id: Uint128, status: Uint128)
     ->Result<Response, ContractError>
 {
-    //-----------check owner--------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized{});
-    }
-//    let x:ProjectState = PROJECTSTATES.load(deps.storage, _project_id.u128().into())?;
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+//    let x:ProjectState = projectstates().load(deps.storage, _project_id.u128().into())?;
     //-------update-------------------------
-    PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
         None => Err(ContractError::NotRegisteredProject {}),
         Some(mut project) => {
             if status == Uint128::zero() {
@@ -187,201 +567,366 @@ pub fn try_setprojectstatus(deps: DepsMut, info: MessageInfo, project_id: Uint12
     Ok(Response::new()
     .add_attribute("action", "Set project status"))
 }
-pub fn convert_str_int(str: String)
-    ->u128
+//-----------ust per share of the project's yield strategy------
+pub fn query_exchange_rate(deps: Deps, x: &ProjectState) -> StdResult<Decimal256>
 {
-    let bytes = str.into_bytes();
-    let mut res: u128 = 0;
-    let mut dot = false;
-    let mut dotbelow = 0;
-
-    for i in 0..bytes.len(){
-        if bytes[i] < 48{
-            dot = true;
-        }
-        else if dotbelow < 6 {
-            res = res * 10 + (bytes[i] - 48) as u128;
-            if dot {
-                dotbelow += 1;
-            }
-        }
-    }
-    return res;
+    let config = CONFIG.load(deps.storage)?;
+    x.yield_strategy.exchange_rate(deps, &config)
 }
-pub fn try_releasemilestone(deps: DepsMut, _env: Env, _project_id: Uint128 ) 
+//-----------ust principal still deposited in anchor------------------
+pub fn try_releasemilestone(mut deps: DepsMut, _env: Env, info: MessageInfo, _project_id: Uint128 ) 
     -> Result<Response, ContractError>
 {
+    check_paused(deps.storage, PauseOperation::Releases)?;
+
     //--------Get project info----------------------------
-    let x:ProjectState = PROJECTSTATES.load(deps.storage, _project_id.u128().into())?;
+    let mut x:ProjectState = projectstates().load(deps.storage, _project_id.u128().into())?;
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Operator, Role::Creator], Some(&x))?;
 
     //--------Checking project status-------------------------
     if x.project_status != ProjectStatus::Releasing{//only releasing status
         return Err(ContractError::NotCorrectStatus{status: x.project_status as u32});
     }
 
-    //---------get hope to release amount---------------------------
+    //--------only milestone that passed the vote-----------------
     let step = x.project_milestonestep.u128() as usize;
-    let release_amount = 
-        x.milestone_states[step].milestone_amount.u128() * UST;
-
-    //---------calc total deposited to anchor----------------
-    //----------map to vec-----------------------
-    let all: StdResult<Vec<_>> = PROJECTSTATES.range(deps.storage, None, None, 
-        cosmwasm_std::Order::Ascending).collect();
-    let all = all.unwrap();
-
-    let mut total_deposited = 0;
-    for x in all{
-        let prj = x.1;
-        total_deposited += prj.communitybacked_amount.u128() + prj.backerbacked_amount.u128();
-
-        for i in 0..(prj.project_milestonestep.u128() as usize){
-            total_deposited -= prj.milestone_states[i].milestone_amount.u128() * UST;
-        }
+    let key = milestone_key(x.project_id, x.project_milestonestep);
+    let mut milestone = MILESTONES.may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::NotFoundMilestoneIndex{})?;
+    if milestone.milestone_status != MILESTONE_APPROVED {
+        return Err(ContractError::NotCorrectMilestoneStatus{
+            step, status: milestone.milestone_status
+        });
     }
-    //----------load config and read aust token address-----------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    
-    //--------get aust balance---------------------
-    let aust_balance: Cw20BalanceResponse = deps.querier.query_wasm_smart(
-        config.aust_token.clone(),
-        &Cw20QueryMsg::Balance{
-            address: _env.contract.address.to_string(),
-        }
-    )?;
 
-    //----------calc declaim aust amount---aust*(release/total)-----------
-    let mut estimate_exchange_rate = total_deposited * UST/aust_balance.balance.u128();
+    //---------get hope to release amount, the last milestone takes the rest of the principal----
+    let next_key = milestone_key(x.project_id, x.project_milestonestep + Uint128::new(1));
+    let next = MILESTONES.may_load(deps.storage, next_key.clone())?;
+    let release_amount = match next {
+        Some(_) => milestone.milestone_amount.u128() * UST,
+        None => (x.communitybacked_amount + x.backerbacked_amount).saturating_sub(x.released_amount).u128(),
+    };
 
-    //--------get exchange rate between ust and aust ---------------------
-    let epoch: EpochStateResponse = deps.querier.query_wasm_smart(
-        config.anchor_market.to_string(),
-        &AnchorQuery::EpochState{
-            block_height: None,
-            distributed_interest: None,
+    //----------load config and get exchange rate of the project's strategy--------
+    let config = CONFIG.load(deps.storage)?;
+    let exchange_rate = x.yield_strategy.exchange_rate(deps.as_ref(), &config)?;
+
+    //----------redeem from the project's own aust, never more than it holds-----------
+    //----------both roundings go down, the remainder stays with the backers-----------
+    let withdraw_amount = ust_to_shares(Uint128::new(release_amount), exchange_rate)?.min(x.aust_amount);
+    let release_amount = shares_to_ust(withdraw_amount, exchange_rate)?;
+    x.aust_amount -= withdraw_amount;
+    x.released_amount += release_amount;
+
+    //----redeem the shares, the creator gets the ust that arrives----------
+    let msgs = redeem_and_pay(deps.branch(), &_env, &x, withdraw_amount,
+        release_amount, RedemptionPayout::Send{ recipient: x.creator_wallet.clone() })?;
+
+    LEDGER.update(deps.storage, |mut ledger| -> StdResult<_> {
+        ledger.total_released += release_amount;
+        Ok(ledger)
+    })?;
+
+    //---------switch to released and move to next milestone---------
+    milestone.milestone_status = MILESTONE_RELEASED;
+    MILESTONES.save(deps.storage, key, &milestone)?;
+    x.project_milestonestep += Uint128::new(1);
+
+    //-----------check milestone done, or open the next vote---------------------
+    match next {
+        None => x.project_status = ProjectStatus::Done, //switch to project done status
+        Some(mut next) => {
+            open_milestone_vote(&mut next, &_env, x.milestone_voteperiod);
+            MILESTONES.save(deps.storage, next_key, &next)?;
         }
-    )?;
-    let epoch_exchange_rate = convert_str_int(epoch.exchange_rate.to_string());
-    
-    if estimate_exchange_rate < epoch_exchange_rate{
-        estimate_exchange_rate = epoch_exchange_rate;
-    }
-
-    let withdraw_amount = release_amount * UST / estimate_exchange_rate;
-    let release_amount = withdraw_amount * epoch_exchange_rate / UST;
-
-    //----ask aust_token for transfer to anchor martket and execute redeem_stable ----------
-    let withdraw = WasmMsg::Execute {
-        contract_addr: String::from(config.aust_token),
-        msg: to_binary(&Cw20ExecuteMsg::Send {
-            contract: config.anchor_market.to_string(),
-            msg: to_binary(&Cw20HookMsg::RedeemStable{}).unwrap(), //redeem_stable{}
-            amount: Uint128::new(withdraw_amount)
-        }).unwrap(),
-        funds: Vec::new()
-    };
+    }
 
-    // return Err(ContractError::Testing{
-    //     aust_balance: aust_balance.balance.to_string(),
-    //     estimate_exchange_rate: estimate_exchange_rate.to_string(),
-    //     epoch_exchange_rate: epoch_exchange_rate.to_string(),
-    //     withdraw_amount: withdraw_amount.to_string(),
-    //     release_amount: release_amount.to_string()
-    // });
-
-    //---------send to creator wallet-------------
-    let ust_release = Coin::new(release_amount, "uusd");
-    let send2_creator = BankMsg::Send { 
-        to_address: x.creator_wallet.to_string(),
-        amount: vec![ust_release] 
-    };
+    projectstates().update(deps.storage, _project_id.u128().into(), |op| match op {
+        None => Err(ContractError::NotRegisteredProject {}),
+        Some(mut project) => {
+            project.project_milestonestep = x.project_milestonestep;
+            project.project_status = x.project_status;
+            project.aust_amount = x.aust_amount;
+            project.released_amount = x.released_amount;
+            Ok(project)
+        }
+    })?;
 
     Ok(Response::new()
-    .add_messages(vec![
-        CosmosMsg::Wasm(withdraw),
-        CosmosMsg::Bank(send2_creator)])
+    .add_submessages(msgs)
     .add_attribute("action", "release milestone")
-    .add_attribute("epoch_exchange_rate", epoch.exchange_rate.to_string())
+    .add_attribute("milestone_step", step.to_string())
+    .add_attribute("exchange_rate", exchange_rate.to_string())
     )
 }
-pub fn try_setmilestonevote(deps: DepsMut, _env:Env, info:MessageInfo, project_id: Uint128, wallet: String, voted: bool)
+//-----------weighted tally: approved/rejected once the result can't change anymore--------
+//-----------or, when the window is closed, by the votes actually cast----------------------
+//-----------total is the backed ust of all backers, the weight that can vote--------------
+pub fn calc_milestone_status(milestone: &Milestone, total: Uint128, quorum: Uint128, threshold: Uint128,
+    closed: bool) -> Uint128
+{
+    let total = total.u128();
+    let yes = milestone.milestone_yes.u128();
+    let no = milestone.milestone_no.u128();
+    let abstain = milestone.milestone_abstain.u128();
+    let not_voted = total.saturating_sub(yes + no + abstain);
+    let quorum_reached = (yes + no + abstain) * 100 >= quorum.u128() * total;
+
+    if closed {
+        if yes > 0 && quorum_reached && yes * 100 >= threshold.u128() * (yes + no) {
+            return MILESTONE_APPROVED;
+        }
+        return MILESTONE_REJECTED;
+    }
+
+    //-------approved even if every remaining weight votes no-------
+    if yes > 0 && quorum_reached && yes * 100 >= threshold.u128() * total.saturating_sub(abstain) {
+        return MILESTONE_APPROVED;
+    }
+    //-------rejected even if every remaining weight votes yes------
+    if (yes + not_voted) * 100 < threshold.u128() * total.saturating_sub(abstain) || not_voted == 0 {
+        return MILESTONE_REJECTED;
+    }
+    MILESTONE_VOTING
+}
+pub fn try_setmilestonevote(deps: DepsMut, _env:Env, info:MessageInfo, project_id: Uint128, wallet: String, vote: VoteOption)
     -> Result<Response, ContractError>
 {
-    let mut x:ProjectState = PROJECTSTATES.load(deps.storage, project_id.u128().into())?;
+    check_paused(deps.storage, PauseOperation::Voting)?;
+
+    let x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
     
     //-------check project status-------------------
     if x.project_status != ProjectStatus::Releasing { //only releasing status
         return Err(ContractError::NotCorrectStatus{status:x.project_status as u32});
     }
 
-    let wallet = deps.api.addr_validate(&wallet).unwrap();
+    let wallet = deps.api.addr_validate(&wallet)?;
     let step = x.project_milestonestep.u128() as usize;
+    let key = milestone_key(project_id, x.project_milestonestep);
+    let mut milestone = MILESTONES.load(deps.storage, key.clone())?;
+
+    //-------only the backer or its registered delegate can vote---------
+    let backer = BACKERS.may_load(deps.storage, (project_id.u128().into(), &wallet))?
+        .ok_or(ContractError::NotBackerWallet{})?;
+    if info.sender != wallet {
+        let delegate = VOTE_DELEGATES.may_load(deps.storage, (project_id.u128().into(), &wallet))?;
+        if delegate != Some(info.sender.clone()) {
+            return Err(ContractError::NotBackerWallet{});
+        }
+    }
 
-    if x.milestone_states[step].milestone_status != Uint128::zero(){//only voting status
+    if milestone.milestone_status != MILESTONE_VOTING{//only voting status
         return Err(ContractError::NotCorrectMilestoneStatus{
-            step:step, status:x.milestone_states[step].milestone_status 
+            step, status:milestone.milestone_status 
         })
     }
 
-    //------set vot status and check all voted for same backer--------------------
-    // let index = x.milestone_states[step].milestone_votes.iter().position(|x|x.wallet == wallet).unwrap();
-    // x.milestone_states[step].milestone_votes[index].voted = voted;
+    //-------only inside the voting window-----------------
+    let now = Uint128::from(_env.block.time.seconds());
+    let open = match (milestone.milestone_votestart, milestone.milestone_voteend) {
+        (Some(start), Some(end)) => now >= start && now <= end,
+        _ => false,
+    };
+    if !open {
+        return Err(ContractError::VotingWindowClosed{});
+    }
 
-    let mut all_voted = true;
-    for vote in x.milestone_states[step].milestone_votes.iter_mut() {
-        if vote.wallet == wallet{
-            vote.voted = voted;
+    //------set vote, every vote of the round is final, weighted by backed ust-----
+    let vote_key = (key.0.clone(), key.1.clone(), &wallet);
+    if let Some(voted) = MILESTONE_VOTES.may_load(deps.storage, vote_key.clone())? {
+        if voted.round == milestone.milestone_round {
+            return Err(ContractError::AlreadyVoted{});
         }
-        all_voted = all_voted & vote.voted;
     }
+    let weight = backer.ust_amount.amount;
+    match vote {
+        VoteOption::Yes => milestone.milestone_yes += weight,
+        VoteOption::No => milestone.milestone_no += weight,
+        VoteOption::Abstain => milestone.milestone_abstain += weight,
+    }
+    MILESTONE_VOTES.save(deps.storage, vote_key, &Vote{
+        wallet: wallet.clone(),
+        weight,
+        vote: Some(vote),
+        submitted_by: Some(info.sender.clone()),
+        submitted_at: Some(now),
+        round: milestone.milestone_round,
+    })?;
 
-    let mut deps = deps;
-    if all_voted{
-        x.milestone_states[step].milestone_status = Uint128::new(1); //switch to releasing status
-        //-----------------release function---------------
-        let res = execute(deps.branch(), _env, info, 
-                    ExecuteMsg::ReleaseMilestone{project_id});
+    //-------resolve to approved or rejected when it is decided--------
+    milestone.milestone_status = calc_milestone_status(
+        &milestone,
+        x.backerbacked_amount,
+        x.milestone_quorum,
+        x.milestone_threshold,
+        false
+    );
 
-        x.milestone_states[step].milestone_status = Uint128::new(2); //switch to released status
-        x.project_milestonestep += Uint128::new(1); //switch to next milestone step
-        
-        //-----------check milestone done---------------------
-        if x.project_milestonestep >= Uint128::new(x.milestone_states.len() as u128){
-            x.project_status = ProjectStatus::Done; //switch to project done status
-        }
+    //-------update-------------------------
+    MILESTONES.save(deps.storage, key, &milestone)?;
 
-        //-------update-------------------------
-        PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
-            None => Err(ContractError::NotRegisteredProject {}),
-            Some(mut project) => {
-                project.milestone_states = x.milestone_states;
-                project.project_milestonestep = x.project_milestonestep;
-                project.project_status = x.project_status;
-                Ok(project)
-            }
-        })?;
-        return res;
+    let res = Response::new()
+        .add_attribute("action", "Set milestone vote")
+        .add_attribute("milestone_status", milestone.milestone_status);
+
+    if milestone.milestone_status == MILESTONE_REJECTED {
+        return after_milestone_rejected(deps, _env, &x, &milestone, res);
     }
-    //-------update-------------------------
-    PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
-        None => Err(ContractError::NotRegisteredProject {}),
-        Some(mut project) => {
-            project.milestone_states = x.milestone_states;
-            project.project_milestonestep = x.project_milestonestep;
-            project.project_status = x.project_status;
-            Ok(project)
+    Ok(res)
+}
+
+pub fn open_milestone_vote(milestone: &mut Milestone, env: &Env, period: Uint128)
+{
+    let now = Uint128::from(env.block.time.seconds());
+    milestone.milestone_votestart = Some(now);
+    milestone.milestone_voteend = Some(now + period);
+}
+
+//-----------rejected milestone: wait for the one resubmission or fail the project---------
+fn after_milestone_rejected(deps: DepsMut, env: Env, x: &ProjectState, milestone: &Milestone, res: Response)
+    -> Result<Response, ContractError>
+{
+    if x.rejection_policy == RejectionPolicy::Resubmit && !milestone.milestone_resubmitted {
+        return Ok(res.add_attribute("next", "resubmit"));
+    }
+
+    let fail = fail_project(deps, env, x.project_id)?;
+    Ok(res
+        .add_submessages(fail.messages)
+        .add_attributes(fail.attributes)
+    )
+}
+
+pub fn try_closemilestonevote(deps: DepsMut, env: Env, project_id: Uint128)
+    -> Result<Response, ContractError>
+{
+    check_paused(deps.storage, PauseOperation::Voting)?;
+    let x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
+
+    //-------check project status-------------------
+    if x.project_status != ProjectStatus::Releasing { //only releasing status
+        return Err(ContractError::NotCorrectStatus{status:x.project_status as u32});
+    }
+
+    let step = x.project_milestonestep.u128() as usize;
+    let key = milestone_key(project_id, x.project_milestonestep);
+    let mut milestone = MILESTONES.load(deps.storage, key.clone())?;
+    if milestone.milestone_status != MILESTONE_VOTING{//only voting status
+        return Err(ContractError::NotCorrectMilestoneStatus{
+            step, status:milestone.milestone_status 
+        })
+    }
+
+    //-------only after the window ended--------------------
+    let now = Uint128::from(env.block.time.seconds());
+    let ended = match milestone.milestone_voteend {
+        Some(end) => now > end,
+        None => false,
+    };
+    if !ended {
+        return Err(ContractError::VotingWindowOpen{});
+    }
+
+    milestone.milestone_status = calc_milestone_status(
+        &milestone,
+        x.backerbacked_amount,
+        x.milestone_quorum,
+        x.milestone_threshold,
+        true
+    );
+
+    MILESTONES.save(deps.storage, key, &milestone)?;
+
+    let res = Response::new()
+        .add_attribute("action", "Close milestone vote")
+        .add_attribute("milestone_status", milestone.milestone_status);
+
+    if milestone.milestone_status == MILESTONE_REJECTED {
+        return after_milestone_rejected(deps, env, &x, &milestone, res);
+    }
+    Ok(res)
+}
+
+pub fn try_resubmitmilestone(deps: DepsMut, env: Env, info: MessageInfo, project_id: Uint128)
+    -> Result<Response, ContractError>
+{
+    check_paused(deps.storage, PauseOperation::Voting)?;
+    let x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Creator], Some(&x))?;
+
+    //-------check project status-------------------
+    if x.project_status != ProjectStatus::Releasing { //only releasing status
+        return Err(ContractError::NotCorrectStatus{status:x.project_status as u32});
+    }
+
+    let step = x.project_milestonestep.u128() as usize;
+    let key = milestone_key(project_id, x.project_milestonestep);
+    let mut milestone = MILESTONES.load(deps.storage, key.clone())?;
+    if milestone.milestone_status != MILESTONE_REJECTED{//only rejected status
+        return Err(ContractError::NotCorrectMilestoneStatus{
+            step, status:milestone.milestone_status 
+        })
+    }
+    if x.rejection_policy != RejectionPolicy::Resubmit || milestone.milestone_resubmitted {
+        return Err(ContractError::AlreadyResubmitted{});
+    }
+
+    //-------a new round leaves the old votes behind, and opens a new window---------
+    milestone.milestone_round += Uint128::new(1);
+    milestone.milestone_yes = Uint128::zero();
+    milestone.milestone_no = Uint128::zero();
+    milestone.milestone_abstain = Uint128::zero();
+    milestone.milestone_status = MILESTONE_VOTING;
+    milestone.milestone_resubmitted = true;
+    open_milestone_vote(&mut milestone, &env, x.milestone_voteperiod);
+
+    MILESTONES.save(deps.storage, key, &milestone)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "Resubmit milestone")
+    .add_attribute("milestone_step", step.to_string())
+    )
+}
+
+pub fn try_setvotedelegate(deps: DepsMut, info: MessageInfo, project_id: Uint128, delegate: Option<String>)
+    -> Result<Response, ContractError>
+{
+    //-------only backers can hand over their vote------------
+    let key = (U128Key::new(project_id.u128()), &info.sender);
+    if BACKERS.may_load(deps.storage, key.clone())?.is_none() {
+        return Err(ContractError::NotBackerWallet{});
+    }
+
+    match delegate.clone() {
+        Some(delegate) => {
+            let delegate = deps.api.addr_validate(&delegate)?;
+            VOTE_DELEGATES.save(deps.storage, key, &delegate)?;
         }
-    })?;
+        None => VOTE_DELEGATES.remove(deps.storage, key),
+    }
 
     Ok(Response::new()
-    .add_attribute("action", "Set milestone vote")
+    .add_attribute("action", "Set vote delegate")
+    .add_attribute("delegate", delegate.unwrap_or_default())
     )
 }
 
-pub fn try_setfundraisingstage(deps: DepsMut, project_id: Uint128, stage: Uint128)
+pub fn try_setfundraisingstage(deps: DepsMut, info: MessageInfo, project_id: Uint128, stage: Uint128)
     -> Result<Response, ContractError>
 {
-    PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+    let x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
+
+    //--------only skip ahead to an existing stage, sold stages never reopen----------
+    if stage.u128() >= x.vesting.len() as u128 {
+        return Err(ContractError::InvalidStage{ stage });
+    }
+    if stage <= x.fundraising_stage {
+        return Err(ContractError::StageNotAhead{ stage });
+    }
+
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
         None => Err(ContractError::NotRegisteredProject {}),
         Some(mut project) => {
             project.fundraising_stage = stage;
@@ -397,13 +942,9 @@ pub fn try_setfundraisingstage(deps: DepsMut, project_id: Uint128, stage: Uint12
 pub fn try_wefundapprove(deps: DepsMut, info:MessageInfo, project_id: Uint128)
     ->Result<Response, ContractError>
 {
-    //-----------check owner--------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    if info.sender != config.owner{
-        return Err(ContractError::Unauthorized{});
-    }
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Operator, Role::ProjectApprover], None)?;
 
-    let mut x:ProjectState = PROJECTSTATES.load(deps.storage, project_id.u128().into())?;
+    let mut x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
     
     //-------check project status-------------------
     if x.project_status != ProjectStatus::WefundVote { //only wefund approve status
@@ -411,7 +952,7 @@ pub fn try_wefundapprove(deps: DepsMut, info:MessageInfo, project_id: Uint128)
     }
     x.project_status = ProjectStatus::Fundraising; //switch to fundraising status
 
-    PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
         None => Err(ContractError::NotRegisteredProject {}),
         Some(mut project) => {
             project.project_status = x.project_status;
@@ -424,14 +965,15 @@ pub fn try_wefundapprove(deps: DepsMut, info:MessageInfo, project_id: Uint128)
     )
 }
 
-pub fn try_removecommunitymember(deps:DepsMut, wallet: String)
+pub fn try_removecommunitymember(deps:DepsMut, info:MessageInfo, wallet: String)
     -> Result<Response, ContractError>
 {
-    let wallet = deps.api.addr_validate(&wallet).unwrap();
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::CommunityManager], None)?;
+    let wallet = deps.api.addr_validate(&wallet)?;
 
     let mut community = COMMUNITY.load(deps.storage).unwrap();
     let res = community.iter().find(|&x| x == &wallet);
-    if res == None {
+    if res.is_none() {
         return Err(ContractError::NotRegisteredCommunity{});
     }
 
@@ -443,14 +985,15 @@ pub fn try_removecommunitymember(deps:DepsMut, wallet: String)
     )
 }
 
-pub fn try_addcommunitymember(deps:DepsMut, wallet: String)
+pub fn try_addcommunitymember(deps:DepsMut, info:MessageInfo, wallet: String)
     -> Result<Response, ContractError>
 {
-    let wallet = deps.api.addr_validate(&wallet).unwrap();
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::CommunityManager], None)?;
+    let wallet = deps.api.addr_validate(&wallet)?;
 
     let mut community = COMMUNITY.load(deps.storage).unwrap();
     let res = community.iter().find(|&x| x == &wallet);
-    if res != None {
+    if res.is_some() {
         return Err(ContractError::AlreadyRegisteredCommunity{});
     }
 
@@ -461,120 +1004,308 @@ pub fn try_addcommunitymember(deps:DepsMut, wallet: String)
     .add_attribute("action", "add community member")
     )
 }
-pub fn try_transferallcoins(deps:DepsMut, _env:Env, info:MessageInfo, wallet:String)
+pub fn try_transferallcoins(deps:DepsMut, info:MessageInfo, wallet:String)
     -> Result<Response, ContractError>
 {
-    //-----------check owner--------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    if info.sender != config.owner{
-        return Err(ContractError::Unauthorized{});
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Treasurer], None)?;
+    let wallet = deps.api.addr_validate(&wallet)
+        .map_err(|_| ContractError::InvalidAddress{})?;
+
+    //--------only the contract fees kept from backings, and aust no project owned at the upgrade-----
+    //--------the rest of the balance is backers'---------------------
+    let amount = CONTRACT_FEES.may_load(deps.storage)?.unwrap_or_default();
+    let aust_amount = CONTRACT_YIELD.may_load(deps.storage)?.unwrap_or_default();
+    if amount.is_zero() && aust_amount.is_zero() {
+        return Err(ContractError::NothingToClaim{});
+    }
+    CONTRACT_FEES.save(deps.storage, &Uint128::zero())?;
+    CONTRACT_YIELD.save(deps.storage, &Uint128::zero())?;
+
+    let mut msgs: Vec<CosmosMsg> = Vec::new();
+    if !amount.is_zero() {
+        msgs.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: wallet.to_string(),
+            amount: vec![Coin::new(amount.u128(), UST_DENOM)],
+        }));
+    }
+    if !aust_amount.is_zero() {
+        let config = CONFIG.load(deps.storage)?;
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: config.aust_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: wallet.to_string(),
+                amount: aust_amount,
+            })?,
+            funds: Vec::new()
+        }));
     }
-    //--------get all native coins and ust - 4 ----------------------
-    let balance: AllBalanceResponse = deps.querier.query(
-        &QueryRequest::Bank(BankQuery::AllBalances {
-            address: _env.contract.address.to_string(),
-        }
-    ))?;
 
-    let mut nativecoins:Vec<Coin> = Vec::new();
-    for mut x in balance.amount
-    {
-        if x.denom == "uusd" {
-            if x.amount.u128() < 4000000 {
-                return Err(ContractError::NeedCoin{});
+    Ok(Response::new()
+    .add_messages(msgs)
+    .add_attribute("action", "trasnfer all coins")
+    .add_attribute("amount", amount)
+    .add_attribute("aust_amount", aust_amount)
+    )
+}
+pub fn try_removeproject(deps:DepsMut, info:MessageInfo, project_id:Uint128)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    remove_project(deps, project_id)
+}
+pub fn try_setprojectstrategy(deps:DepsMut, info:MessageInfo, project_id:Uint128, strategy:YieldStrategy)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Operator], None)?;
+    validate_strategy(deps.as_ref(), &strategy)?;
+
+    //--------nothing deposited yet, only before fundraising----------
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
+        None => Err(ContractError::NotRegisteredProject {}),
+        Some(mut project) => {
+            if project.project_status != ProjectStatus::WefundVote {
+                return Err(ContractError::NotCorrectStatus{ status: project.project_status as u32 });
             }
-            x.amount = Uint128::new(x.amount.u128() - 4000000);
+            project.yield_strategy = strategy;
+            Ok(project)
         }
-        nativecoins.push(x);
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set project strategy"))
+}
+pub fn try_setfeeschedule(deps:DepsMut, info:MessageInfo, fee_schedule:FeeSchedule)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+    check_fee_schedule(deps.as_ref(), &fee_schedule)?;
+
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.fee_schedule = fee_schedule;
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set fee schedule"))
+}
+//-----------None goes back to the config schedule---------------
+pub fn try_setprojectfeeschedule(deps:DepsMut, info:MessageInfo, project_id:Uint128,
+    fee_schedule:Option<FeeSchedule>) -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+    if let Some(fee_schedule) = &fee_schedule {
+        check_fee_schedule(deps.as_ref(), fee_schedule)?;
     }
 
-    let bank_native = BankMsg::Send { 
-        to_address: wallet.clone(),
-        amount: nativecoins,
-    };
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
+        None => Err(ContractError::NotRegisteredProject {}),
+        Some(mut project) => {
+            project.fee_schedule = fee_schedule;
+            Ok(project)
+        }
+    })?;
 
-    //--------transfer all aust--------------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
+    Ok(Response::new()
+        .add_attribute("action", "set project fee schedule")
+        .add_attribute("project_id", project_id))
+}
+pub fn remove_project(deps:DepsMut, _project_id:Uint128)
+    ->Result<Response, ContractError>
+{
+    let res = projectstates().may_load(deps.storage, _project_id.u128().into());
+    if res == Ok(None) {
+        return Err(ContractError::NotRegisteredProject {});
+    }
+    remove_projectstate(deps.storage, _project_id)?;
+    Ok(Response::new())
+}
+//-----------keep the old address if none, fail on invalid one---------------
+fn validate_or(deps: Deps, addr: Option<String>, default: Addr)
+    -> Result<Addr, ContractError>
+{
+    match addr {
+        Some(addr) => deps.api.addr_validate(&addr)
+            .map_err(|_| ContractError::InvalidAddress{}),
+        None => Ok(default),
+    }
+}
+pub fn try_proposenewowner(deps:DepsMut, env:Env, info:MessageInfo, new_owner: String, expires_in: Uint128)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    let new_owner = deps.api.addr_validate(&new_owner)
+        .map_err(|_| ContractError::InvalidAddress{})?;
+    let expires = Uint128::from(env.block.time.seconds()).checked_add(expires_in)
+        .map_err(StdError::from)?;
+
+    let mut config = CONFIG.load(deps.storage)?;
+    config.pending_owner = Some(OwnershipProposal{ owner: new_owner.clone(), expires });
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose new owner")
+        .add_attribute("new_owner", new_owner)
+        .add_attribute("expires", expires))
+}
+pub fn try_acceptownership(deps:DepsMut, env:Env, info:MessageInfo)
+    -> Result<Response, ContractError>
+{
+    let mut config = CONFIG.load(deps.storage)?;
+    let proposal = config.pending_owner.ok_or(ContractError::NoOwnershipProposal{})?;
+
+    if info.sender != proposal.owner {
+        return Err(ContractError::Unauthorized{});
+    }
+    if Uint128::from(env.block.time.seconds()) > proposal.expires {
+        return Err(ContractError::OwnershipProposalExpired{});
+    }
+
+    config.owner = proposal.owner;
+    config.pending_owner = None;
+    CONFIG.save(deps.storage, &config)?;
 
-    let aust_balance: Cw20BalanceResponse = deps.querier.query_wasm_smart(
-        config.aust_token.clone(),
-        &Cw20QueryMsg::Balance{
-            address: _env.contract.address.to_string(),
+    Ok(Response::new()
+        .add_attribute("action", "accept ownership")
+        .add_attribute("owner", config.owner))
+}
+pub fn try_cancelownershipproposal(deps:DepsMut, info:MessageInfo)
+    -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.pending_owner.is_none() {
+        return Err(ContractError::NoOwnershipProposal{});
+    }
+    config.pending_owner = None;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel ownership proposal"))
+}
+//-----------vault addresses of a strategy must be valid---------------
+fn validate_strategy(deps: Deps, strategy: &YieldStrategy) -> Result<(), ContractError>
+{
+    if let YieldStrategy::Vault{ vault, share_token } = strategy {
+        for addr in &[vault, share_token] {
+            deps.api.addr_validate(addr.as_str())
+                .map_err(|_| ContractError::InvalidAddress{})?;
         }
-    )?;
-    let bank_aust = WasmMsg::Execute {
-        contract_addr: String::from(config.aust_token),
-        msg: to_binary(&Cw20ExecuteMsg::Transfer {
-            recipient: wallet,
-            amount: aust_balance.balance,
-        }).unwrap(),
-        funds: Vec::new()
+    }
+    Ok(())
+}
+//-----------optional address, fail on invalid one---------------
+fn validate_opt(deps: Deps, addr: Option<String>) -> Result<Option<Addr>, ContractError>
+{
+    addr.map(|addr| deps.api.addr_validate(&addr)
+            .map_err(|_| ContractError::InvalidAddress{}))
+        .transpose()
+}
+#[allow(clippy::too_many_arguments)]
+pub fn try_proposeconfigchange(deps:DepsMut, env:Env, info:MessageInfo,
+    anchor_market: Option<String>,
+    aust_token: Option<String>,
+    vesting_contract: Option<String>,
+    timelock_period: Option<Uint128>,
+    yield_strategy: Option<YieldStrategy>
+) -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::ConfigManager], None)?;
+
+    let anchor_market = validate_opt(deps.as_ref(), anchor_market)?;
+    let aust_token = validate_opt(deps.as_ref(), aust_token)?;
+    let vesting_contract = validate_opt(deps.as_ref(), vesting_contract)?;
+    if let Some(strategy) = &yield_strategy {
+        validate_strategy(deps.as_ref(), strategy)?;
+    }
+    if anchor_market.is_none() && aust_token.is_none() && vesting_contract.is_none()
+        && timelock_period.is_none() && yield_strategy.is_none() {
+        return Err(ContractError::EmptyConfigChange{});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let id = CONFIG_CHANGE_SEQ.load(deps.storage)? + Uint128::new(1);
+    CONFIG_CHANGE_SEQ.save(deps.storage, &id)?;
+
+    let proposed_at = Uint128::from(env.block.time.seconds());
+    let change = ConfigChange{
+        id,
+        anchor_market,
+        aust_token,
+        vesting_contract,
+        timelock_period,
+        yield_strategy,
+        proposed_at,
+        executable_at: proposed_at + config.timelock_period,
     };
+    CONFIG_CHANGES.save(deps.storage, id.u128().into(), &change)?;
 
     Ok(Response::new()
-    .add_messages(vec![
-        CosmosMsg::Bank(bank_native),
-        CosmosMsg::Wasm(bank_aust)])
-    .add_attribute("action", "trasnfer all coins")
-    )
+        .add_attribute("action", "propose config change")
+        .add_attribute("id", id)
+        .add_attribute("executable_at", change.executable_at))
 }
-pub fn try_removeproject(deps:DepsMut, info:MessageInfo, project_id:Uint128)
+pub fn try_executeconfigchange(deps:DepsMut, env:Env, info:MessageInfo, id:Uint128)
     -> Result<Response, ContractError>
 {
-    //-----------check owner--------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    if info.sender != config.owner
-    {
-        return Err(ContractError::Unauthorized{});
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::ConfigManager], None)?;
+
+    let change = CONFIG_CHANGES.load(deps.storage, id.u128().into())?;
+    if Uint128::from(env.block.time.seconds()) < change.executable_at {
+        return Err(ContractError::ConfigChangeTimelocked{ executable_at: change.executable_at });
     }
 
-    return remove_project(deps, project_id);
+    let mut config = CONFIG.load(deps.storage)?;
+    config.anchor_market = change.anchor_market.unwrap_or(config.anchor_market);
+    config.aust_token = change.aust_token.unwrap_or(config.aust_token);
+    config.vesting_contract = change.vesting_contract.unwrap_or(config.vesting_contract);
+    config.timelock_period = change.timelock_period.unwrap_or(config.timelock_period);
+    config.yield_strategy = change.yield_strategy.unwrap_or(config.yield_strategy);
+    CONFIG.save(deps.storage, &config)?;
+
+    CONFIG_CHANGES.remove(deps.storage, id.u128().into());
+
+    Ok(Response::new()
+        .add_attribute("action", "execute config change")
+        .add_attribute("id", id))
 }
-pub fn remove_project(deps:DepsMut, _project_id:Uint128)
-    ->Result<Response, ContractError>
+pub fn try_cancelconfigchange(deps:DepsMut, info:MessageInfo, id:Uint128)
+    -> Result<Response, ContractError>
 {
-    let res = PROJECTSTATES.may_load(deps.storage, _project_id.u128().into());
-    if res == Ok(None) {
-        return Err(ContractError::NotRegisteredProject {});
-    }
-    PROJECTSTATES.remove(deps.storage, U128Key::new(_project_id.u128()));
-    Ok(Response::new())
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner], None)?;
+
+    CONFIG_CHANGES.load(deps.storage, id.u128().into())?;
+    CONFIG_CHANGES.remove(deps.storage, id.u128().into());
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel config change")
+        .add_attribute("id", id))
 }
 pub fn try_setconfig(deps:DepsMut, _env:Env, info:MessageInfo,
-    admin: Option<String>, 
     wefund: Option<String>, 
-    anchor_market: Option<String>, 
-    aust_token: Option<String>,
-    vesting_contract: Option<String>
+    community_manager: Option<String>,
+    yield_split: Option<YieldSplit>,
+    referral_share: Option<Uint128>
 ) -> Result<Response, ContractError>
 {
-    //-----------check owner--------------------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized{});
-    }
-    
-    let mut config = CONFIG.load(deps.storage).unwrap();
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::ConfigManager], None)?;
 
-    config.owner =  admin
-    .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-    .unwrap_or(config.owner);
+    let mut config = CONFIG.load(deps.storage)?;
 
-    config.wefund = wefund
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(config.wefund);
-
-    config.anchor_market = anchor_market
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(config.anchor_market);
-
-    config.aust_token = aust_token
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(config.aust_token);
-
-    config.vesting_contract = vesting_contract
-        .and_then(|s| deps.api.addr_validate(s.as_str()).ok()) 
-        .unwrap_or(config.vesting_contract);
+    config.wefund = validate_or(deps.as_ref(), wefund, config.wefund)?;
+    config.community_manager = validate_or(deps.as_ref(), community_manager, config.community_manager)?;
+    if let Some(yield_split) = yield_split {
+        check_yield_split(&yield_split)?;
+        config.yield_split = yield_split;
+    }
+    if let Some(referral_share) = referral_share {
+        if referral_share > Uint128::new(100) {
+            return Err(ContractError::InvalidReferralShare{ share: referral_share });
+        }
+        config.referral_share = referral_share;
+    }
 
     CONFIG.save(deps.storage, &config)?;
 
@@ -582,210 +1313,520 @@ pub fn try_setconfig(deps:DepsMut, _env:Env, info:MessageInfo,
         .add_attribute("action", "SetConfig"))                                
 }
 pub fn try_completeproject(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _env: Env,
+    info: MessageInfo,
     _project_id: Uint128
 ) -> Result<Response, ContractError>
 {
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Operator], None)?;
+    check_paused(deps.storage, PauseOperation::Releases)?;
+
     //--------Get project info----------------------------
-    let x:ProjectState = PROJECTSTATES.load(deps.storage, _project_id.u128().into())?;
+    let x:ProjectState = projectstates().load(deps.storage, _project_id.u128().into())?;
 
     //--------Checking project status-------------------------
     if x.project_status != ProjectStatus::Releasing{//only releasing status
         return Err(ContractError::NotCorrectStatus{status: x.project_status as u32});
     }
 
-    //---------calc hope to release amount---------------------------
-    let mut release_amount: u128 = x.communitybacked_amount.u128() + x.backerbacked_amount.u128();
-    
-    for i in 0..(x.project_milestonestep.u128() as usize){
-        release_amount -= x.milestone_states[i].milestone_amount.u128() * UST;
+    //--------the current milestone must have passed its vote, the rest are released with it-----
+    let current = MILESTONES.may_load(deps.storage, milestone_key(x.project_id, x.project_milestonestep))?
+        .ok_or(ContractError::NotFoundMilestoneIndex{})?;
+    if current.milestone_status != MILESTONE_APPROVED {
+        return Err(ContractError::NotCorrectMilestoneStatus{
+            step: x.project_milestonestep.u128() as usize, status: current.milestone_status
+        });
+    }
+    let mut milestones = Vec::new();
+    let mut step = x.project_milestonestep;
+    while let Some(milestone) = MILESTONES.may_load(deps.storage, milestone_key(x.project_id, step))? {
+        milestones.push((milestone_key(x.project_id, step), milestone));
+        step += Uint128::new(1);
     }
 
-    //---------calc total deposited to anchor----------------
-    //----------map to vec-----------------------
-    let all: StdResult<Vec<_>> = PROJECTSTATES.range(deps.storage, None, None, 
-        cosmwasm_std::Order::Ascending).collect();
-    let all = all.unwrap();
-
-    let mut total_deposited = 0;
-    for x in all{
-        let prj = x.1;
-        total_deposited += prj.communitybacked_amount.u128() + prj.backerbacked_amount.u128();
+    //---------calc hope to release amount, the backed ust not paid out yet---------
+    let backed = x.communitybacked_amount + x.backerbacked_amount;
+    let release_amount = backed.saturating_sub(x.released_amount);
 
-        for i in 0..(prj.project_milestonestep.u128() as usize){
-            total_deposited -= prj.milestone_states[i].milestone_amount.u128() * UST;
-        }
-    }
     //----------load config and read aust token address-----------------
     let config = CONFIG.load(deps.storage).unwrap();
-    
-    //--------get aust balance---------------------
-    let aust_balance: Cw20BalanceResponse = deps.querier.query_wasm_smart(
-        config.aust_token.clone(),
-        &Cw20QueryMsg::Balance{
-            address: _env.contract.address.to_string(),
-        }
-    )?;
 
-    //----------calc declaim aust amount---aust*(release/total)-----------
-    let mut estimate_exchange_rate = total_deposited * UST/aust_balance.balance.u128();
+    //--------get exchange rate of the project's strategy ---------------------
+    let exchange_rate = x.yield_strategy.exchange_rate(deps.as_ref(), &config)?;
 
-    //--------get exchange rate between ust and aust ---------------------
-    let epoch: EpochStateResponse = deps.querier.query_wasm_smart(
-        config.anchor_market.to_string(),
-        &AnchorQuery::EpochState{
-            block_height: None,
-            distributed_interest: None,
-        }
-    )?;
-    let epoch_exchange_rate = convert_str_int(epoch.exchange_rate.to_string());
-    
-    if estimate_exchange_rate < epoch_exchange_rate{
-        estimate_exchange_rate = epoch_exchange_rate;
-    }
-
-    let withdraw_amount = release_amount * UST / estimate_exchange_rate;
-    let release_amount = withdraw_amount * epoch_exchange_rate / UST;
-
-    //----ask aust_token for transfer to anchor martket and execute redeem_stable ----------
-    let withdraw = WasmMsg::Execute {
-        contract_addr: String::from(config.aust_token),
-        msg: to_binary(&Cw20ExecuteMsg::Send {
-            contract: config.anchor_market.to_string(),
-            msg: to_binary(&Cw20HookMsg::RedeemStable{}).unwrap(), //redeem_stable{}
-            amount: Uint128::new(withdraw_amount)
-        }).unwrap(),
-        funds: Vec::new()
-    };
+    //----------redeem the rest of the principal from the project's own aust-----------
+    let withdraw_amount = ust_to_shares(release_amount, exchange_rate)?.min(x.aust_amount);
+    let release_amount = shares_to_ust(withdraw_amount, exchange_rate)?;
 
-    //---------send to creator wallet-------------
-    let ust_release = Coin::new(release_amount, "uusd");
-    let send2_creator = BankMsg::Send { 
-        to_address: x.creator_wallet.to_string(),
-        amount: vec![ust_release] 
-    };
+    //----redeem the shares, the creator gets the ust that arrives----------
+    let msgs = redeem_and_pay(deps.branch(), &_env, &x, withdraw_amount,
+        release_amount, RedemptionPayout::Send{ recipient: x.creator_wallet.clone() })?;
+
+    LEDGER.update(deps.storage, |mut ledger| -> StdResult<_> {
+        ledger.total_released += release_amount;
+        Ok(ledger)
+    })?;
+
+    for (key, mut milestone) in milestones {
+        milestone.milestone_status = MILESTONE_RELEASED;
+        MILESTONES.save(deps.storage, key, &milestone)?;
+    }
+
+    //-----update project state to DONE, nothing left to release----------
+    projectstates().update(deps.storage, _project_id.u128().into(), |op| match op {
+        None => Err(ContractError::NotRegisteredProject {}),
+        Some(mut project) => {
+            project.project_status = ProjectStatus::Done;
+            project.aust_amount -= withdraw_amount;
+            project.released_amount += release_amount;
+            Ok(project)
+        }
+    })?;
 
     Ok(Response::new()
-    .add_messages(vec![
-        CosmosMsg::Wasm(withdraw),
-        CosmosMsg::Bank(send2_creator)])
+    .add_submessages(msgs)
     .add_attribute("action", "complete project")
-    .add_attribute("epoch_exchange_rate", epoch.exchange_rate.to_string())
+    .add_attribute("exchange_rate", exchange_rate.to_string())
     )
 }
 pub fn try_failproject(
     deps: DepsMut,
     _env: Env,
+    info: MessageInfo,
+    _project_id: Uint128
+) -> Result<Response, ContractError>
+{
+    check_roles(deps.as_ref(), &info.sender, &[Role::Owner, Role::Operator], None)?;
+    fail_project(deps, _env, _project_id)
+}
+pub fn fail_project(
+    mut deps: DepsMut,
+    _env: Env,
     _project_id: Uint128
 ) -> Result<Response, ContractError>
 {
     //--------Get project info----------------------------
-    let x:ProjectState = PROJECTSTATES.load(deps.storage, _project_id.u128().into())?;
+    let x:ProjectState = projectstates().load(deps.storage, _project_id.u128().into())?;
 
-    //--------Checking project status-------------------------
-    if x.project_status != ProjectStatus::Releasing{//only releasing status
+    //--------Checking project status, fundraising that can't reach its cap fails too-------
+    if x.project_status != ProjectStatus::Releasing && x.project_status != ProjectStatus::Fundraising {
         return Err(ContractError::NotCorrectStatus{status: x.project_status as u32});
     }
 
-    //---------calc hope to release amount---------------------------
-    let mut release_amount: u128 = x.communitybacked_amount.u128() + x.backerbacked_amount.u128();
-    
-    for i in 0..(x.project_milestonestep.u128() as usize){
-        release_amount -= x.milestone_states[i].milestone_amount.u128() * UST;
+    //---------backed ust not paid out yet, refunded with its yield---------
+    let principal = (x.communitybacked_amount + x.backerbacked_amount).saturating_sub(x.released_amount);
+
+    //----------load config and read aust token address-----------------
+    let config = CONFIG.load(deps.storage).unwrap();
+
+    //--------get exchange rate of the project's strategy ---------------------
+    let exchange_rate = x.yield_strategy.exchange_rate(deps.as_ref(), &config)?;
+
+    //----------redeem all aust of the project, yield goes back to the backers too-----------
+    let withdraw_amount = x.aust_amount;
+    let release_amount = shares_to_ust(withdraw_amount, exchange_rate)?;
+
+    //----redeem the shares, the ust that arrives goes to the refund pool----------
+    let msgs = redeem_and_pay(deps.branch(), &_env, &x, withdraw_amount,
+        release_amount, RedemptionPayout::RefundPool{})?;
+
+    LEDGER.update(deps.storage, |mut ledger| -> StdResult<_> {
+        ledger.total_refunded += principal;
+        Ok(ledger)
+    })?;
+
+    //-----update project state to FAIL----------------------------
+    projectstates().update(deps.storage, _project_id.u128().into(), |op| match op {
+        None => Err(ContractError::NotRegisteredProject {}),
+        Some(mut project) => {
+            project.project_status = ProjectStatus::Fail; //fail
+            project.aust_amount = Uint128::zero();
+            Ok(project)
+        }
+    })?;
+
+    Ok(Response::new()
+    .add_submessages(msgs)
+    .add_attribute("action", "project failed")
+    .add_attribute("refund_amount", release_amount.to_string())
+    )
+}
+
+//-----------aust minted for the wallet's backings and for all backings of the project------
+pub fn calc_backer_share(storage: &dyn Storage, x: &ProjectState, wallet: &Addr)
+    -> StdResult<(Uint128, Uint128)>
+{
+    let mut backed = Uint128::zero();
+    for map in &[BACKERS, COMMUNITY_BACKERS] {
+        if let Some(backer) = map.may_load(storage, (x.project_id.u128().into(), wallet))? {
+            backed += backer.aust_amount.amount;
+        }
+    }
+    Ok((backed, x.backer_aust))
+}
+
+//-----------pro-rata share of the refund pool, for backers and community backers--------
+pub fn calc_refund_claimable(storage: &dyn Storage, x: &ProjectState, wallet: &Addr)
+    -> StdResult<Uint128>
+{
+    let pool = match REFUND_POOLS.may_load(storage, x.project_id.u128().into())? {
+        Some(pool) => pool,
+        None => return Ok(Uint128::zero()),
+    };
+
+    //--------share of the pool by aust minted for the wallet's backings---------
+    let (backed, total_backed) = calc_backer_share(storage, x, wallet)?;
+    if backed.is_zero() || total_backed.is_zero() {
+        return Ok(Uint128::zero());
+    }
+    let share = pool.multiply_ratio(backed, total_backed);
+    let claimed = REFUNDS_CLAIMED.may_load(storage, (x.project_id.u128().into(), wallet))?
+        .unwrap_or_default();
+
+    Ok(share.checked_sub(claimed).unwrap_or_default())
+}
+
+pub fn try_claimrefund(deps: DepsMut, info: MessageInfo, project_id: Uint128)
+    -> Result<Response, ContractError>
+{
+    let x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
+
+    //--------only failed project-------------------------
+    if x.project_status != ProjectStatus::Fail{
+        return Err(ContractError::NotCorrectStatus{status: x.project_status as u32});
     }
 
-    //---------calc total deposited to anchor----------------
-    //----------map to vec-----------------------
-    let all: StdResult<Vec<_>> = PROJECTSTATES.range(deps.storage, None, None, 
-        cosmwasm_std::Order::Ascending).collect();
-    let all = all.unwrap();
+    let amount = calc_refund_claimable(deps.storage, &x, &info.sender)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim{});
+    }
+
+    REFUNDS_CLAIMED.update(deps.storage, (project_id.u128().into(), &info.sender),
+        |claimed| -> StdResult<_> { Ok(claimed.unwrap_or_default() + amount) }
+    )?;
+
+    let send2_backer = BankMsg::Send { 
+        to_address: info.sender.to_string(),
+        amount: vec![Coin::new(amount.u128(), UST_DENOM)] 
+    };
+
+    Ok(Response::new()
+    .add_message(CosmosMsg::Bank(send2_backer))
+    .add_attribute("action", "claim refund")
+    .add_attribute("amount", amount)
+    )
+}
 
-    let mut total_deposited = 0;
-    for x in all{
-        let prj = x.1;
-        total_deposited += prj.communitybacked_amount.u128() + prj.backerbacked_amount.u128();
+//-----------10^decimals of the project token, 1 when it has none yet----------------
+//-----------backers and the community each fill half of the collected amount---------
+pub fn needs_backing(x: &ProjectState, community: bool) -> bool
+{
+    let collected = Uint128::new(x.project_collected.u128() / 2 * UST);
+    if community {
+        x.communitybacked_amount < collected
+    } else {
+        x.backerbacked_amount < collected
+    }
+}
+pub fn token_unit(deps: Deps, x: &ProjectState) -> StdResult<Uint128>
+{
+    if x.token_addr == "" {
+        return Ok(Uint128::new(1));
+    }
+    let token_info: TokenInfoResponse = deps.querier.query_wasm_smart(
+        x.token_addr.clone(),
+        &Cw20QueryMsg::TokenInfo{}
+    )?;
+    Ok(Uint128::new(10u128.pow(token_info.decimals as u32)))
+}
 
-        for i in 0..(prj.project_milestonestep.u128() as usize){
-            total_deposited -= prj.milestone_states[i].milestone_amount.u128() * UST;
+//-----------first stage from start still selling: not past its stage_end, not sold out-------
+//-----------returns the stage and the tokens left in it, in the smallest token unit----------
+pub fn open_stage(storage: &dyn Storage, x: &ProjectState, start: usize, now: u64, unit: Uint128)
+    -> StdResult<Option<(usize, Uint128)>>
+{
+    for (stage, param) in x.vesting.iter().enumerate().skip(start) {
+        if let Some(end) = param.stage_end {
+            if now >= end.u128() as u64 {
+                continue;
+            }
+        }
+        let sold = TOKENS_SOLD.may_load(storage, (x.project_id.u128().into(), (stage as u128).into()))?
+            .unwrap_or_default();
+        let available = param.stage_amount.checked_mul(unit)?.checked_sub(sold).unwrap_or_default();
+        if !available.is_zero() {
+            return Ok(Some((stage, available)));
         }
     }
-    //----------load config and read aust token address-----------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    
-    //--------get aust balance---------------------
-    let aust_balance: Cw20BalanceResponse = deps.querier.query_wasm_smart(
-        config.aust_token.clone(),
-        &Cw20QueryMsg::Balance{
-            address: _env.contract.address.to_string(),
+    Ok(None)
+}
+
+//-----------tokens the backed ust buys from the open stage on, each stage at its own price------
+//-----------a stage that sells out passes the rest of the ust on to the next one----------------
+//-----------returns (stage, amount) per stage and moves the project's fundraising_stage---------
+fn allocate_tokens(deps: DepsMut, env: &Env, x: &mut ProjectState, backed: Uint128)
+    -> Result<Vec<(Uint128, Uint128)>, ContractError>
+{
+    let unit = token_unit(deps.as_ref(), x)?;
+    let now = env.block.time.seconds();
+    let mut stage = x.fundraising_stage.u128() as usize;
+    let mut rest = backed;
+    let mut allocations: Vec<(Uint128, Uint128)> = Vec::new();
+
+    loop {
+        let (open, available) = match open_stage(deps.storage, x, stage, now, unit)? {
+            Some(open) => open,
+            None => {
+                //-------what a sold out last stage leaves is dust, anything more finds no tokens-----
+                let dust = match allocations.last() {
+                    Some((last, _)) => rest.multiply_ratio(unit,
+                        x.vesting[last.u128() as usize].stage_price).is_zero(),
+                    None => false,
+                };
+                if dust {
+                    break;
+                }
+                return Err(ContractError::StageSoldOut{});
+            }
+        };
+        stage = open;
+        let price = x.vesting[stage].stage_price;
+        if price.is_zero() {
+            return Err(ContractError::InvalidStage{ stage: Uint128::new(stage as u128) });
         }
-    )?;
+        let amount = rest.multiply_ratio(unit, price).min(available);
+        if amount.is_zero() {
+            break; //less than the smallest token unit left
+        }
+        //-------the tokens of a stage sold out are paid rounded up---------
+        let cost = if amount == available {
+            let cost = amount.checked_mul(price).map_err(StdError::from)?;
+            (cost + unit - Uint128::new(1)).checked_div(unit).map_err(StdError::from)?.min(rest)
+        } else {
+            rest
+        };
+        allocations.push((Uint128::new(stage as u128), amount));
+        rest -= cost;
+        if rest.is_zero() || amount < available {
+            break;
+        }
+        stage += 1;
+    }
+
+    for (stage, amount) in allocations.iter() {
+        TOKENS_SOLD.update(deps.storage, (x.project_id.u128().into(), stage.u128().into()),
+            |sold| -> StdResult<_> { Ok(sold.unwrap_or_default() + *amount) })?;
+    }
+    x.fundraising_stage = Uint128::new(stage as u128);
+    Ok(allocations)
+}
+
+//-----------ust of a backing: what is backed, what wefund gets, what the contract keeps-------
+pub struct FeeBreakdown{
+    pub tier: usize,
+    pub backed: Uint128,
+    pub wefund_fee: Uint128,
+    pub contract_fee: Uint128,
+}
+
+pub fn calc_fee(schedule: &FeeSchedule, amount: Uint128) -> Result<FeeBreakdown, ContractError>
+{
+    if amount < schedule.min_contribution {
+        return Err(ContractError::NeedCoin{});
+    }
+    let tier = schedule.tiers.iter().rposition(|tier| tier.min_amount <= amount)
+        .ok_or(ContractError::NeedCoin{})?;
+    let fee = &schedule.tiers[tier];
+
+    //-------percent is charged on top, the backed part rounds down-------------
+    let percent_fee = amount - amount.multiply_ratio(100u128, fee.percent.u128() + 100);
+    let total_fee = percent_fee.checked_add(fee.flat_fee).map_err(StdError::from)?;
+    let backed = amount.checked_sub(total_fee).ok().filter(|backed| !backed.is_zero())
+        .ok_or(ContractError::FeeExceedsBacking{ amount })?;
+    let wefund_fee = total_fee.checked_sub(fee.contract_fee)
+        .map_err(|_| ContractError::FeeExceedsBacking{ amount })?;
+
+    Ok(FeeBreakdown{ tier, backed, wefund_fee, contract_fee: fee.contract_fee })
+}
 
-    //----------calc declaim aust amount---aust*(release/total)-----------
-    let mut estimate_exchange_rate = total_deposited * UST/aust_balance.balance.u128();
+//-----------tiers ascending, and every tier leaves something to back from its lowest amount-----
+//-----------recipients are valid addresses with weights summing to 100---------------------------
+fn check_fee_schedule(deps: Deps, schedule: &FeeSchedule) -> Result<(), ContractError>
+{
+    let ascending = schedule.tiers.windows(2).all(|pair| pair[0].min_amount < pair[1].min_amount);
+    if schedule.tiers.is_empty() || !ascending {
+        return Err(ContractError::InvalidFeeSchedule{});
+    }
+    for tier in schedule.tiers.iter() {
+        let lowest = tier.min_amount.max(schedule.min_contribution);
+        calc_fee(schedule, lowest).map_err(|_| ContractError::InvalidFeeSchedule{})?;
+    }
 
-    //--------get exchange rate between ust and aust ---------------------
-    let epoch: EpochStateResponse = deps.querier.query_wasm_smart(
-        config.anchor_market.to_string(),
-        &AnchorQuery::EpochState{
-            block_height: None,
-            distributed_interest: None,
+    if !schedule.recipients.is_empty() {
+        let total: Uint128 = schedule.recipients.iter().map(|recipient| recipient.weight).sum();
+        if total != Uint128::new(100) {
+            return Err(ContractError::InvalidFeeWeights{ total });
         }
-    )?;
-    let epoch_exchange_rate = convert_str_int(epoch.exchange_rate.to_string());
-    
-    if estimate_exchange_rate < epoch_exchange_rate{
-        estimate_exchange_rate = epoch_exchange_rate;
     }
+    for recipient in schedule.recipients.iter() {
+        deps.api.addr_validate(recipient.address.as_str())
+            .map_err(|_| ContractError::InvalidAddress{})?;
+    }
+    Ok(())
+}
+
+//-----------wefund's part of a fee by weight, the rounding remainder goes into the next fee------
+//-----------shares below MIN_FEE_PAYOUT wait until the recipient is owed enough-----------------
+fn split_fee(storage: &mut dyn Storage, schedule: &FeeSchedule, wefund: &Addr, fee: Coin)
+    -> StdResult<Vec<SubMsg>>
+{
+    let everything = [FeeRecipient{ address: wefund.clone(), weight: Uint128::new(100) }];
+    let recipients = if schedule.recipients.is_empty() { &everything[..] } else { &schedule.recipients[..] };
+
+    let total = fee.amount + FEE_REMAINDER.may_load(storage)?.unwrap_or_default();
+    let mut remainder = total;
+    let mut msgs = Vec::new();
+    for recipient in recipients {
+        let share = total.multiply_ratio(recipient.weight, 100u128);
+        remainder = remainder.checked_sub(share)?;
+        msgs.extend(pay_fee(storage, &recipient.address, Coin::new(share.u128(), fee.denom.clone()))?);
+    }
+    FEE_REMAINDER.save(storage, &remainder)?;
+    Ok(msgs)
+}
+
+//-----------send what the address is owed, or keep it accruing below MIN_FEE_PAYOUT-----------
+fn pay_fee(storage: &mut dyn Storage, address: &Addr, fee: Coin) -> StdResult<Option<SubMsg>>
+{
+    let owed = ACCRUED_FEES.may_load(storage, address)?.unwrap_or_default() + fee.amount;
+    if owed < MIN_FEE_PAYOUT {
+        ACCRUED_FEES.save(storage, address, &owed)?;
+        return Ok(None);
+    }
+    ACCRUED_FEES.remove(storage, address);
+    Ok(Some(SubMsg::new(BankMsg::Send{
+        to_address: address.to_string(),
+        amount: vec![Coin::new(owed.u128(), fee.denom)],
+    })))
+}
+
+//-----------fees still accrued for the sender, whatever their size-----------------
+pub fn try_claimfees(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError>
+{
+    let amount = ACCRUED_FEES.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim{});
+    }
+    ACCRUED_FEES.remove(deps.storage, &info.sender);
 
-    let withdraw_amount = release_amount * UST / estimate_exchange_rate;
-    let release_amount = withdraw_amount * epoch_exchange_rate / UST;
+    Ok(Response::new()
+    .add_message(BankMsg::Send{
+        to_address: info.sender.to_string(),
+        amount: vec![Coin::new(amount.u128(), UST_DENOM)],
+    })
+    .add_attribute("action", "claim fees")
+    .add_attribute("amount", amount)
+    )
+}
 
-    let mut msg= Vec::new();
+fn check_yield_split(split: &YieldSplit) -> Result<(), ContractError>
+{
+    if split.backers + split.creator + split.wefund != Uint128::new(100) {
+        return Err(ContractError::InvalidYieldSplit{
+            backers: split.backers, creator: split.creator, wefund: split.wefund
+        });
+    }
+    Ok(())
+}
 
-    //----ask aust_token for transfer to anchor martket and execute redeem_stable ----------
-    let withdraw = WasmMsg::Execute {
-        contract_addr: String::from(config.aust_token),
-        msg: to_binary(&Cw20ExecuteMsg::Send {
-            contract: config.anchor_market.to_string(),
-            msg: to_binary(&Cw20HookMsg::RedeemStable{}).unwrap(), //redeem_stable{}
-            amount: Uint128::new(withdraw_amount)
-        }).unwrap(),
-        funds: Vec::new()
+//-----------aust of the project above its principal, counting yield already claimed--------
+//-----------the principal is what was backed and not paid out, done projects may keep some---
+//-----------failed projects put everything in the refund pool, so they have none------------
+pub fn calc_project_yield(x: &ProjectState, rate: Decimal256) -> StdResult<Uint128>
+{
+    let principal = match x.project_status {
+        ProjectStatus::Fundraising | ProjectStatus::Releasing | ProjectStatus::Done =>
+            (x.backerbacked_amount + x.communitybacked_amount).saturating_sub(x.released_amount),
+        _ => return Ok(Uint128::zero()),
     };
+    //-------round the principal up, yield never eats into it---------
+    let principal_aust = ust_to_shares_ceil(principal, rate)?;
+
+    Ok((x.aust_amount + x.yield_claimed).checked_sub(principal_aust)
+        .unwrap_or_default())
+}
+
+//-----------aust of yield the wallet can claim, as backer, creator and wefund----------
+pub fn calc_yield_claimable(storage: &dyn Storage, x: &ProjectState, wallet: &Addr, rate: Decimal256)
+    -> StdResult<Uint128>
+{
+    let config = CONFIG.load(storage)?;
+    let split = config.yield_split;
+    let total = calc_project_yield(x, rate)?;
+    if total.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    let mut share = Uint128::zero();
+    if wallet == &x.creator_wallet {
+        share += total.multiply_ratio(split.creator, 100u128);
+    }
+    if wallet == &config.wefund {
+        share += total.multiply_ratio(split.wefund, 100u128);
+    }
+    let (backed, total_backed) = calc_backer_share(storage, x, wallet)?;
+    if !backed.is_zero() {
+        share += total.multiply_ratio(split.backers, 100u128).multiply_ratio(backed, total_backed);
+    }
 
-    msg.push(CosmosMsg::Wasm(withdraw));
+    let claimed = YIELD_CLAIMED.may_load(storage, (x.project_id.u128().into(), wallet))?
+        .unwrap_or_default();
+    Ok(share.checked_sub(claimed).unwrap_or_default())
+}
 
-    //---------send to backer wallet-------------
-    for backer in x.backer_states{
-        let mut backed_ust = backer.ust_amount.clone(); 
+pub fn try_claimyield(mut deps: DepsMut, env: Env, info: MessageInfo, project_id: Uint128)
+    -> Result<Response, ContractError>
+{
+    check_paused(deps.storage, PauseOperation::Releases)?;
 
-        //---while mistone releasing, suddenly failed, distribute with %
-        backed_ust.amount = Uint128::new(backer.ust_amount.amount.u128() * release_amount
-            /(x.communitybacked_amount.u128() + x.backerbacked_amount.u128()));
+    let mut x:ProjectState = projectstates().load(deps.storage, project_id.u128().into())?;
+    let config = CONFIG.load(deps.storage)?;
+    let rate = x.yield_strategy.exchange_rate(deps.as_ref(), &config)?;
 
-        let send2_backer = BankMsg::Send { 
-            to_address: backer.backer_wallet.to_string(),
-            amount: vec![backed_ust] 
-        };
-        msg.push(CosmosMsg::Bank(send2_backer));
+    let withdraw_amount = calc_yield_claimable(deps.storage, &x, &info.sender, rate)?;
+    let amount = shares_to_ust(withdraw_amount, rate)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim{});
     }
-    
-    //-----update project state to FAIL----------------------------
-    PROJECTSTATES.update(deps.storage, _project_id.u128().into(), |op| match op {
+
+    YIELD_CLAIMED.update(deps.storage, (project_id.u128().into(), &info.sender),
+        |claimed| -> StdResult<_> { Ok(claimed.unwrap_or_default() + withdraw_amount) }
+    )?;
+    x.aust_amount -= withdraw_amount;
+    x.yield_claimed += withdraw_amount;
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
         None => Err(ContractError::NotRegisteredProject {}),
         Some(mut project) => {
-            project.project_status = ProjectStatus::Fail; //fail
+            project.aust_amount = x.aust_amount;
+            project.yield_claimed = x.yield_claimed;
             Ok(project)
         }
     })?;
 
+    //----redeem the shares, the claimer gets the ust that arrives----------
+    let msgs = redeem_and_pay(deps.branch(), &env, &x, withdraw_amount,
+        amount, RedemptionPayout::Send{ recipient: info.sender.clone() })?;
+
     Ok(Response::new()
-    .add_messages(msg)
-    .add_attribute("action", "project failed")
+    .add_submessages(msgs)
+    .add_attribute("action", "claim yield")
+    .add_attribute("aust_amount", withdraw_amount)
+    .add_attribute("amount", amount.to_string())
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn try_addproject(
     deps:DepsMut,
     _env: Env,
@@ -806,10 +1847,52 @@ pub fn try_addproject(
     _project_teammembers: Vec<TeamMember>,
     _vesting: Vec<VestingParameter>,
     _token_addr: String,
+    _milestone_quorum: Option<Uint128>,
+    _milestone_threshold: Option<Uint128>,
+    _milestone_voteperiod: Option<Uint128>,
+    _rejection_policy: Option<RejectionPolicy>,
 ) -> Result<Response, ContractError> 
 {
+    check_paused(deps.storage, PauseOperation::ProjectCreation)?;
+
+    //---------check milestone vote rule-------------------
+    let milestone_quorum = _milestone_quorum.unwrap_or(DEFAULT_MILESTONE_QUORUM);
+    let milestone_threshold = _milestone_threshold.unwrap_or(DEFAULT_MILESTONE_THRESHOLD);
+    if milestone_quorum > Uint128::new(100) || milestone_threshold.is_zero()
+        || milestone_threshold > Uint128::new(100)
+    {
+        return Err(ContractError::InvalidVoteRule{
+            quorum: milestone_quorum, threshold: milestone_threshold
+        });
+    }
+
     let token_addr = deps.api.addr_validate(_token_addr.as_str())
-        .unwrap_or(Addr::unchecked("".to_string()));
+        .unwrap_or_else(|_| Addr::unchecked("".to_string()));
+
+    //---------milestones never release more than is collected----------
+    let mut milestone_total = Uint128::zero();
+    for milestone in _project_milestones.iter() {
+        milestone_total = milestone_total.checked_add(milestone.milestone_amount).map_err(StdError::from)?;
+    }
+    if milestone_total > _project_collected {
+        return Err(ContractError::MilestonesOverCollected{
+            total: milestone_total, collected: _project_collected
+        });
+    }
+
+    //---------milestones always start from voting, never pre-approved----
+    let mut _project_milestones = _project_milestones;
+    for milestone in _project_milestones.iter_mut(){
+        milestone.milestone_status = MILESTONE_VOTING;
+        milestone.milestone_votes = Vec::new();
+        milestone.milestone_votestart = None;
+        milestone.milestone_voteend = None;
+        milestone.milestone_resubmitted = false;
+        milestone.milestone_round = Uint128::zero();
+        milestone.milestone_yes = Uint128::zero();
+        milestone.milestone_no = Uint128::zero();
+        milestone.milestone_abstain = Uint128::zero();
+    }
 
     let mut new_project:ProjectState = ProjectState{
         project_company: _project_company,
@@ -831,12 +1914,23 @@ pub fn try_addproject(
 
         backerbacked_amount: Uint128::zero(),
         communitybacked_amount: Uint128::zero(),
+        aust_amount: Uint128::zero(),
+        backer_aust: Uint128::zero(),
+        yield_claimed: Uint128::zero(),
+        yield_strategy: CONFIG.load(deps.storage)?.yield_strategy, //config default, until SetProjectStrategy
+        redemption_shortfall: Uint128::zero(),
+        released_amount: Uint128::zero(),
+        fee_schedule: None, //config schedule, until SetProjectFeeSchedule
 
         backer_states: Vec::new(),
         communitybacker_states: Vec::new(),
 
         milestone_states: _project_milestones,
         project_milestonestep: Uint128::zero(), //first milestonestep
+        milestone_quorum,
+        milestone_threshold,
+        milestone_voteperiod: _milestone_voteperiod.unwrap_or(DEFAULT_MILESTONE_VOTEPERIOD),
+        rejection_policy: _rejection_policy.unwrap_or(RejectionPolicy::Resubmit),
 
         teammember_states: _project_teammembers,
 
@@ -847,7 +1941,7 @@ pub fn try_addproject(
     save_projectstate(deps.storage, &mut new_project)?;
 
     let config = CONFIG.load(deps.storage)?;
-    if config.vesting_contract != "".to_string() && token_addr != "".to_string()
+    if config.vesting_contract != "" && token_addr != ""
     {
         let mut vesting_params: Vec<VestingParam> = Vec::new();
         for param in _vesting{
@@ -865,7 +1959,7 @@ pub fn try_addproject(
                     project_id: new_project.project_id,
                     admin: _env.contract.address.to_string(),
                     token_addr: token_addr.to_string(),
-                    vesting_params: vesting_params,
+                    vesting_params,
                     start_time: Uint128::zero()
                 }
             ).unwrap(),
@@ -883,126 +1977,137 @@ pub fn try_addproject(
         .add_attribute("action", "add project"))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn try_back2project(
-    deps: DepsMut, 
+    mut deps: DepsMut, 
     env: Env,
     info: MessageInfo,
     project_id: Uint128, 
     backer_wallet: String,
-    fundraising_stage: Uint128,
-    token_amount: Uint128,
     otherchain: String,
     otherchain_wallet: String,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> 
 {
+    check_paused(deps.storage, PauseOperation::Backing)?;
+
     //-------check project exist-----------------------------------
-    let res = PROJECTSTATES.may_load(deps.storage, project_id.u128().into());
+    let res = projectstates().may_load(deps.storage, project_id.u128().into());
     if res == Ok(None) { //not exist
         return Err(ContractError::NotRegisteredProject {});
     }
     //--------Get project info------------------------------------
-    let mut x = PROJECTSTATES.load(deps.storage, project_id.u128().into())?;
+    let mut x = projectstates().load(deps.storage, project_id.u128().into())?;
     if x.project_status != ProjectStatus::Fundraising{//only fundraising status
         return Err(ContractError::NotCorrectStatus{status: x.project_status as u32});
     }
 
     //--------check sufficient back--------------------
-    let fee:u128 = 4 * UST;
-    if info.funds.is_empty() || info.funds[0].amount.u128() < 6 * UST{
+    if info.funds.is_empty() {
         return Err(ContractError::NeedCoin{});
     }
- 
+    if info.funds.len() != 1 || info.funds[0].denom != UST_DENOM {
+        return Err(ContractError::WrongDenom{});
+    }
+
     let fund = info.funds[0].clone();
     let mut fund_real_back = fund.clone();
     let mut fund_wefund = fund.clone();
-    //--------calc amount to desposit and to wefund
-    if fund.amount.u128() >= 100 * UST{
-        fund_real_back.amount = Uint128::new(fund.amount.u128() * 100 / 105);
-        fund_wefund.amount = Uint128::new((fund.amount.u128() * 5 / 105) - fee);
+    //--------calc amount to desposit and to wefund, by the project's fee schedule
+    let config = CONFIG.load(deps.storage)?;
+    let fee = calc_fee(x.fee_schedule.as_ref().unwrap_or(&config.fee_schedule), fund.amount)?;
+    fund_real_back.amount = fee.backed;
+    fund_wefund.amount = fee.wefund_fee;
+    let kept = CONTRACT_FEES.may_load(deps.storage)?.unwrap_or_default();
+    CONTRACT_FEES.save(deps.storage, &(kept + fee.contract_fee))?;
+
+    //--------tokens for the backed ust at the stage prices, none when the project sells no tokens
+    let allocations = if x.vesting.is_empty() {
+        Vec::new()
     } else {
-        fund_real_back.amount = Uint128::new(fund.amount.u128() - 5 * UST);
-        fund_wefund.amount = Uint128::new(1 * UST);
-    }
+        allocate_tokens(deps.branch(), &env, &mut x, fund_real_back.amount)?
+    };
 
-    let backer_wallet = deps.api.addr_validate(&backer_wallet).unwrap();
+    let backer_wallet = deps.api.addr_validate(&backer_wallet)
+        .map_err(|_| ContractError::InvalidAddress{})?;
+
+    //--------no referring yourself or your own project---------------
+    let referrer = match referrer {
+        Some(referrer) => {
+            let referrer = deps.api.addr_validate(&referrer)?;
+            if referrer == backer_wallet || referrer == info.sender || referrer == x.creator_wallet {
+                return Err(ContractError::InvalidReferrer{});
+            }
+            Some(referrer)
+        }
+        None => None,
+    };
 
     //--------check community and calc backed amount----------------
     let community = COMMUNITY.load(deps.storage)?;
     let is_community = community.iter().find(|&x| x == &backer_wallet);
-    let collected = Uint128::new(x.project_collected.u128() / 2 * UST);
+    if !needs_backing(&x, is_community.is_some()) {
+        return Err(ContractError::AlreadyCollected{});
+    }
 
-    if is_community != None { //community backer
-        if x.communitybacked_amount >= collected{
-            return Err(ContractError::AlreadyCollected{});
-        }
+    if is_community.is_some() { //community backer
         x.communitybacked_amount += fund_real_back.amount;
     } else { //only backer
-        if x.backerbacked_amount >= collected{
-            return Err(ContractError::AlreadyCollected{});
-        }
         x.backerbacked_amount += fund_real_back.amount;
     }
-    //------push to new backer------------------
+    LEDGER.update(deps.storage, |mut ledger| -> StdResult<_> {
+        ledger.total_deposited += fund_real_back.amount;
+        Ok(ledger)
+    })?;
+    //------shares are credited by the deposit reply, or at once when the ust stays here----
+    let deposit = x.yield_strategy.deposit_msg(&config, fund_real_back.clone())?;
+    if deposit.is_some() {
+        PENDING_DEPOSIT.save(deps.storage, &PendingDeposit{
+            project_id,
+            wallet: backer_wallet.clone(),
+            community: is_community.is_some(),
+            ust_amount: fund_real_back.amount,
+        })?;
+    }
+    let wallet = backer_wallet.clone();
+    //------add to backers, one entry per wallet------------------
     let new_baker:BackerState = BackerState{
-        backer_wallet: backer_wallet,
-        otherchain: otherchain,
-        otherchain_wallet: otherchain_wallet,
+        backer_wallet,
+        otherchain,
+        otherchain_wallet,
         ust_amount: fund_real_back.clone(),
         aust_amount: Coin::new(0, "aust")
     };
-    if is_community != None {//community backer
-        x.communitybacker_states.push(new_baker);
-    } else {
-        x.backer_states.push(new_baker);
+    add_backer(deps.storage, project_id, new_baker, is_community.is_some())?;
+    if deposit.is_none() {
+        add_backer_aust(deps.storage, project_id, &wallet, is_community.is_some(), fund_real_back.amount)?;
     }
 
     //------check needback-----------------
-    let mut communitybacker_needback = true;
-    let mut backer_needback = true;
-
-    if x.communitybacked_amount  >= collected{
-        communitybacker_needback = false;
-    }
-    if x.backerbacked_amount  >= collected{
-        backer_needback = false;
-    }
+    let communitybacker_needback = needs_backing(&x, true);
+    let backer_needback = needs_backing(&x, false);
 
-    let mut msgs: Vec<CosmosMsg> = vec![];
+    let mut msgs: Vec<SubMsg> = vec![];
 
     //---------check collection and switch to releasing status---------
-    if communitybacker_needback == false && backer_needback == false{
+    if !communitybacker_needback && !backer_needback{
         x.project_status = ProjectStatus::Releasing; //releasing
 
-        //------add milestone votes in every milestone---------------
-        let mut milestone_votes = Vec::new();
-        for backer in x.backer_states.clone(){
-            milestone_votes.push(
-                Vote{ wallet: backer.backer_wallet, voted: false }
-            );
+        //------open the vote on the first milestone, votes are kept as they are cast----
+        let key = milestone_key(project_id, Uint128::zero());
+        if let Some(mut first) = MILESTONES.may_load(deps.storage, key.clone())? {
+            open_milestone_vote(&mut first, &env, x.milestone_voteperiod);
+            MILESTONES.save(deps.storage, key, &first)?;
         }
-        //-----add wefund vote------------------
         let config = CONFIG.load(deps.storage)?;
-        milestone_votes.push(
-            Vote{ wallet: config.owner, voted: true}
-        );
-
-        for i in 0..(x.milestone_states.len() as usize){
-            x.milestone_states[i].milestone_votes = milestone_votes.clone();
-        }
 
-        if config.vesting_contract != "".to_string() && x.token_addr != "".to_string() {
+        if config.vesting_contract != "" && x.token_addr != "" {
             let vesting = x.vesting.clone();
             let mut token_amount = Uint128::zero();
             for stage in vesting {
                 token_amount += stage.stage_amount;
             }
-
-            let token_info: TokenInfoResponse = deps.querier.query_wasm_smart(
-                x.token_addr.clone(),
-                &Cw20QueryMsg::TokenInfo{}
-            )?;
-            token_amount = token_amount * 
-                Uint128::new((10 as u128).pow(token_info.decimals as u32)); //for decimals
+            token_amount *= token_unit(deps.as_ref(), &x)?; //for decimals
             
             let token_transfer = WasmMsg::Execute {
                 contract_addr: x.token_addr.to_string(),
@@ -1015,7 +2120,7 @@ pub fn try_back2project(
                 ).unwrap(),
                 funds: vec![]
             };
-            msgs.push(CosmosMsg::Wasm(token_transfer));
+            msgs.push(SubMsg::new(token_transfer));
             //---------start vesting-----------------------------
             let start_vesting = WasmMsg::Execute {
                 contract_addr: config.vesting_contract.to_string(),
@@ -1027,65 +2132,72 @@ pub fn try_back2project(
                 ).unwrap(),
                 funds: vec![]
             };
-            msgs.push(CosmosMsg::Wasm(start_vesting));
+            msgs.push(SubMsg::new(start_vesting));
         }
     }
 
-    PROJECTSTATES.update(deps.storage, project_id.u128().into(), |op| match op {
+    projectstates().update(deps.storage, project_id.u128().into(), |op| match op {
         None => Err(ContractError::NotRegisteredProject {}),
         Some(mut project) => {
             project.project_status = x.project_status.clone();
             project.communitybacked_amount = x.communitybacked_amount;
             project.backerbacked_amount = x.backerbacked_amount;
-            project.backer_states = x.backer_states;
-            project.communitybacker_states = x.communitybacker_states;
-            
-            if x.project_status == ProjectStatus::Releasing{//only on switching releasing status
-                project.milestone_states = x.milestone_states;
-            }
+            project.fundraising_stage = x.fundraising_stage;
             Ok(project)
         }
     })?;
 
     //----------load config and read anchor market address-----------------
-    let config = CONFIG.load(deps.storage).unwrap();
-    let anchormarket = config.anchor_market;
-
-    //----------deposite to anchor market------------------------
-    let deposite_project = WasmMsg::Execute {
-            contract_addr: String::from(anchormarket),
-            msg: to_binary(&AnchorMarket::DepositStable {}).unwrap(),
-            funds: vec![fund_real_back]
-    };
-    msgs.push(CosmosMsg::Wasm(deposite_project));
+    //----------deposite to the project's yield strategy------------------------
+    if let Some(deposite_project) = deposit {
+        msgs.push(SubMsg::reply_on_success(deposite_project, DEPOSIT_REPLY_ID));
+    }
 
-    //---------send to Wefund with 5/105--------------------
-    let bank_wefund = BankMsg::Send { 
-        to_address: config.wefund.to_string(),
-        amount: vec![fund_wefund] 
-    };
-    msgs.push(CosmosMsg::Bank(bank_wefund));
+    //---------the referrer's share comes out of wefund's fee--------------------
+    let mut referral_fee = Uint128::zero();
+    if let Some(referrer) = &referrer {
+        referral_fee = fund_wefund.amount.multiply_ratio(config.referral_share, 100u128);
+        fund_wefund.amount -= referral_fee;
+        msgs.extend(pay_fee(deps.storage, referrer, Coin::new(referral_fee.u128(), fund_wefund.denom.clone()))?);
+        add_referral(deps.storage, referrer, project_id, fund_real_back.amount, referral_fee)?;
+    }
 
-    let x = PROJECTSTATES.load(deps.storage, project_id.u128().into())?;
-    if config.vesting_contract != "".to_string() && x.token_addr != "".to_string() {
-        //----------add fundraising user------------------------
-        let add_fundraising_user = WasmMsg::Execute {
-            contract_addr: config.vesting_contract.to_string(),
-            msg: to_binary(
-                &VestingMsg::AddUser {
-                    project_id: x.project_id,
-                    wallet: info.sender,
-                    stage: fundraising_stage,
-                    amount: token_amount,
-                }
-            ).unwrap(),
-            funds: vec![]
-        };
-        msgs.push(CosmosMsg::Wasm(add_fundraising_user));
+    //---------split the fee between the recipients, the contract fee stays here--------------------
+    let fee_schedule = x.fee_schedule.as_ref().unwrap_or(&config.fee_schedule);
+    msgs.extend(split_fee(deps.storage, fee_schedule, &config.wefund, fund_wefund)?);
+    let fee_event = Event::new("fee")
+        .add_attribute("project_id", project_id)
+        .add_attribute("amount", fund.amount)
+        .add_attribute("tier", fee.tier.to_string())
+        .add_attribute("backed", fee.backed)
+        .add_attribute("wefund_fee", fee.wefund_fee)
+        .add_attribute("contract_fee", fee.contract_fee)
+        .add_attribute("referrer", referrer.map(|referrer| referrer.to_string()).unwrap_or_default())
+        .add_attribute("referral_fee", referral_fee);
+
+    let x = projectstates().load(deps.storage, project_id.u128().into())?;
+    for (stage, token_amount) in allocations {
+        if config.vesting_contract != "" && x.token_addr != "" {
+            //----------add fundraising user, once per stage------------------------
+            let add_fundraising_user = WasmMsg::Execute {
+                contract_addr: config.vesting_contract.to_string(),
+                msg: to_binary(
+                    &VestingMsg::AddUser {
+                        project_id: x.project_id,
+                        wallet: wallet.clone(),
+                        stage,
+                        amount: token_amount,
+                    }
+                ).unwrap(),
+                funds: vec![]
+            };
+            msgs.push(SubMsg::new(add_fundraising_user));
+        }
     }
 
     Ok(Response::new()
-    .add_messages(msgs)
+    .add_submessages(msgs)
+    .add_event(fee_event)
     .add_attribute("action", "back to project")
     )
 }
@@ -1,7 +1,7 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 use cosmwasm_std::{Uint128};
-use crate::state::{ProjectStatus};
+use crate::state::PauseOperation;
 
 #[derive(Error, Debug)]
 pub enum ContractError {
@@ -11,6 +11,36 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Cannot migrate from contract {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from version {stored} down to {current}")]
+    CannotDowngrade { stored: String, current: String },
+
+    #[error("Projects are still being migrated")]
+    MigrationInProgress {},
+
+    #[error("Config change is still timelocked until {executable_at}")]
+    ConfigChangeTimelocked { executable_at: Uint128 },
+
+    #[error("Config change proposal has no changes")]
+    EmptyConfigChange {},
+
+    #[error("Operation is paused: {operation:?}")]
+    Paused { operation: PauseOperation },
+
+    #[error("No ownership proposal")]
+    NoOwnershipProposal {},
+
+    #[error("Ownership proposal expired")]
+    OwnershipProposalExpired {},
+
+    #[error("Role can't be granted")]
+    NotGrantableRole {},
+
+    #[error("Role is not granted")]
+    NotGrantedRole {},
+
     #[error("Project id is already registerd")]
     AlreadyRegisteredProject {},
 
@@ -20,6 +50,9 @@ pub enum ContractError {
     #[error("Need some coin")]
     NeedCoin{},
 
+    #[error("Only uusd can back a project")]
+    WrongDenom{},
+
     #[error("Alreay enough collected")]
     AlreadyCollected{},
 
@@ -49,6 +82,55 @@ pub enum ContractError {
     #[error("Not backer wallet")]
     NotBackerWallet{},
 
+    #[error("Invalid milestone vote rule : quorum {quorum}% threshold {threshold}%")]
+    InvalidVoteRule{
+        quorum: Uint128, threshold: Uint128,
+    },
+
+    #[error("Milestone vote is not open")]
+    VotingWindowClosed{},
+
+    #[error("Milestone vote is still open")]
+    VotingWindowOpen{},
+
+    #[error("Milestone already resubmitted")]
+    AlreadyResubmitted{},
+
+    #[error("Nothing to claim")]
+    NothingToClaim{},
+
+    #[error("Invalid yield split : backers {backers}% creator {creator}% wefund {wefund}%")]
+    InvalidYieldSplit{
+        backers: Uint128, creator: Uint128, wefund: Uint128,
+    },
+
+    #[error("Invalid fee schedule")]
+    InvalidFeeSchedule{},
+
+    #[error("Fee recipient weights sum to {total}%, not 100%")]
+    InvalidFeeWeights{ total: Uint128 },
+
+    #[error("Referral share over 100% : {share}")]
+    InvalidReferralShare{ share: Uint128 },
+
+    #[error("Backer and project creator can't be the referrer")]
+    InvalidReferrer{},
+
+    #[error("Not a fundraising stage with a price : {stage}")]
+    InvalidStage{ stage: Uint128 },
+
+    #[error("Fundraising stage only moves forward : {stage}")]
+    StageNotAhead{ stage: Uint128 },
+
+    #[error("All fundraising stages are sold out or closed")]
+    StageSoldOut{},
+
+    #[error("Fees take all of the backing : {amount}")]
+    FeeExceedsBacking{ amount: Uint128 },
+
+    #[error("Unknown reply id : {id}")]
+    UnknownReply{ id: u64 },
+
     #[error("Not found Milestone index")]
     NotFoundMilestoneIndex{},
 
@@ -57,14 +139,11 @@ pub enum ContractError {
         step:usize, status: Uint128,
     },
 
-    #[error("Not correct Milestone status : {aust_balance} {estimate_exchange_rate} {epoch_exchange_rate} {withdraw_amount} {release_amount}")]
-    Testing{
-        aust_balance: String,
-        estimate_exchange_rate: String,
-        epoch_exchange_rate: String,
-        withdraw_amount: String,
-        release_amount: String
-    }
+    #[error("Milestones add up to {total}, more than the {collected} collected")]
+    MilestonesOverCollected{
+        total: Uint128, collected: Uint128,
+    },
+
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -0,0 +1,313 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, DepsMut, Env, StdResult, Storage, Uint128};
+use cw20::{Cw20QueryMsg, BalanceResponse as Cw20BalanceResponse};
+use cosmwasm_bignumber::Decimal256;
+use cw_storage_plus::{Item, Map, U128Key};
+
+use crate::state::{Config, CONFIG, BackerState, TeamMember, VestingParameter, ProjectStatus,
+    ProjectState, Milestone, Vote, VoteOption, RejectionPolicy, PauseState, PAUSE_STATE,
+    CONFIG_CHANGE_SEQ, PROJECT_SEQ, DEFAULT_MILESTONE_QUORUM, DEFAULT_MILESTONE_THRESHOLD,
+    DEFAULT_MILESTONE_VOTEPERIOD, DEFAULT_TIMELOCK_PERIOD, MILESTONE_VOTING, store_projectstate,
+    projectstates, Ledger, LEDGER, YieldSplit, FeeSchedule, CONTRACT_FEES, CONTRACT_YIELD};
+use crate::contract::{UST, UST_DENOM};
+use crate::strategy::{YieldStrategy, ust_to_shares};
+
+//------------layouts stored by 17.2.x, before roles and normalized projects---------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyConfig {
+    pub owner: Addr,
+    pub wefund: Addr,
+    pub anchor_market: Addr,
+    pub aust_token: Addr,
+    pub vesting_contract: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyVote{
+    pub wallet: Addr,
+    pub voted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyMilestone{
+    pub milestone_step: Uint128,
+    pub milestone_name: String,
+    pub milestone_description: String,
+    pub milestone_startdate: String,
+    pub milestone_enddate: String,
+    pub milestone_amount: Uint128,
+    pub milestone_status: Uint128, //0:voting, 1:releasing 2:released
+    pub milestone_votes: Vec<LegacyVote>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyProjectState{
+    pub project_company: String,
+    pub project_title: String,
+    pub project_description: String,
+    pub project_ecosystem: String,
+    pub project_createddate: String,
+    pub project_saft: String,
+    pub project_logo: String,
+    pub project_whitepaper: String,
+    pub project_website: String,
+    pub project_email: String,
+    pub project_id: Uint128,
+    pub creator_wallet: Addr,
+    pub project_collected: Uint128,
+    pub project_status: ProjectStatus,
+    pub fundraising_stage: Uint128,
+    pub backerbacked_amount: Uint128,
+    pub communitybacked_amount: Uint128,
+    pub backer_states: Vec<BackerState>,
+    pub communitybacker_states: Vec<BackerState>,
+    pub milestone_states: Vec<LegacyMilestone>,
+    pub project_milestonestep: Uint128,
+    pub teammember_states: Vec<TeamMember>,
+    pub vesting: Vec<VestingParameter>,
+    pub token_addr: Addr,
+}
+
+//------------same keys, read with the old layout when the current one fails-------
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+pub const LEGACY_PROJECTS: Map<U128Key, LegacyProjectState> = Map::new("prj");
+
+//------------last project id looked at by the paged migration, gone once it is done-------
+pub const MIGRATION_CURSOR: Item<Uint128> = Item::new("migration_cursor");
+
+//------------returns true when the old layout was found, its projects need converting too----
+pub fn migrate_config(store: &mut dyn Storage) -> StdResult<bool>
+{
+    let legacy = CONFIG.load(store).is_err();
+    if legacy {
+        let legacy = LEGACY_CONFIG.load(store)?;
+        CONFIG.save(store, &Config{
+            community_manager: legacy.owner.clone(),
+            owner: legacy.owner,
+            wefund: legacy.wefund,
+            anchor_market: legacy.anchor_market,
+            aust_token: legacy.aust_token,
+            vesting_contract: legacy.vesting_contract,
+            pending_owner: None,
+            timelock_period: DEFAULT_TIMELOCK_PERIOD,
+            yield_split: YieldSplit::default(),
+            yield_strategy: YieldStrategy::Anchor,
+            fee_schedule: FeeSchedule::default(),
+            referral_share: Uint128::zero(),
+        })?;
+    }
+    if CONFIG_CHANGE_SEQ.may_load(store)?.is_none() {
+        CONFIG_CHANGE_SEQ.save(store, &Uint128::zero())?;
+    }
+    if PAUSE_STATE.may_load(store)?.is_none() {
+        PAUSE_STATE.save(store, &PauseState::default())?;
+    }
+    if LEDGER.may_load(store)?.is_none() {
+        LEDGER.save(store, &Ledger::default())?;
+    }
+    Ok(legacy)
+}
+
+//------------the old release kept fees and yield in the contract balance, TransferAllCoins drained it----
+//------------take it all now, migrate_projects takes back what each project owns---------
+pub fn seed_contract_balances(deps: DepsMut, env: &Env) -> StdResult<()>
+{
+    let config = CONFIG.load(deps.storage)?;
+    let ust = deps.querier.query_balance(env.contract.address.clone(), UST_DENOM)?.amount;
+    let aust: Cw20BalanceResponse = deps.querier.query_wasm_smart(
+        config.aust_token,
+        &Cw20QueryMsg::Balance{ address: env.contract.address.to_string() }
+    )?;
+    CONTRACT_FEES.save(deps.storage, &ust)?;
+    CONTRACT_YIELD.save(deps.storage, &aust.balance)
+}
+
+//------------convert up to limit project ids after the cursor, returns true when done-------
+//------------the exchange rate is only queried for projects holding deposits------------
+pub fn migrate_projects(deps: DepsMut, env: &Env, limit: usize) -> StdResult<bool>
+{
+    let cursor = match MIGRATION_CURSOR.may_load(deps.storage)? {
+        Some(cursor) => cursor.u128(),
+        None => return Ok(true),
+    };
+    let last = PROJECT_SEQ.may_load(deps.storage)?.unwrap_or_default().u128();
+    let end = last.min(cursor + limit as u128);
+
+    let mut rate = None;
+    for id in (cursor + 1)..=end {
+        if projectstates().may_load(deps.storage, id.into()).is_err() {
+            let legacy = LEGACY_PROJECTS.load(deps.storage, id.into())?;
+
+            //-------old deposits are all in anchor, shares taken at its rate-------
+            let strategy = YieldStrategy::Anchor;
+            let deposited = matches!(legacy.project_status, ProjectStatus::Fundraising | ProjectStatus::Releasing);
+            if deposited && rate.is_none() {
+                let config = CONFIG.load(deps.storage)?;
+                rate = Some(strategy.exchange_rate(deps.as_ref(), &config)?);
+            }
+
+            //-------raw remove, the indexed map can't read the old layout-------
+            LEGACY_PROJECTS.remove(deps.storage, id.into());
+            LEDGER.update(deps.storage, |ledger| add_to_ledger(ledger, &legacy))?;
+            let mut x = upgrade_project(legacy, env, strategy, if deposited { rate } else { None })?;
+            store_projectstate(deps.storage, &mut x)?;
+
+            //-------the project's shares are its principal, not the contract's-------
+            let seeded = if x.yield_strategy == YieldStrategy::Hold { CONTRACT_FEES } else { CONTRACT_YIELD };
+            let kept = seeded.may_load(deps.storage)?.unwrap_or_default();
+            seeded.save(deps.storage, &kept.saturating_sub(x.aust_amount))?;
+        }
+    }
+    if end >= last {
+        MIGRATION_CURSOR.remove(deps.storage);
+        return Ok(true);
+    }
+    MIGRATION_CURSOR.save(deps.storage, &Uint128::new(end))?;
+    Ok(false)
+}
+
+//------------principal of an old project: backed, released by step, then done or refunded----
+fn add_to_ledger(mut ledger: Ledger, legacy: &LegacyProjectState) -> StdResult<Ledger>
+{
+    if legacy.project_status == ProjectStatus::WefundVote {
+        return Ok(ledger);
+    }
+    let backed = legacy.backerbacked_amount + legacy.communitybacked_amount;
+    let released = released_principal(legacy);
+    let rest = backed.checked_sub(released)?;
+
+    ledger.total_deposited += backed;
+    ledger.total_released += released;
+    match legacy.project_status {
+        ProjectStatus::Done => ledger.total_released += rest,
+        ProjectStatus::Fail => ledger.total_refunded += rest,
+        _ => {}
+    }
+    Ok(ledger)
+}
+
+fn released_principal(legacy: &LegacyProjectState) -> Uint128
+{
+    let mut released = Uint128::zero();
+    for milestone in legacy.milestone_states.iter().take(legacy.project_milestonestep.u128() as usize) {
+        released += milestone.milestone_amount * Uint128::new(UST);
+    }
+    released
+}
+
+fn upgrade_project(mut legacy: LegacyProjectState, env: &Env, strategy: YieldStrategy, rate: Option<Decimal256>)
+    -> StdResult<ProjectState>
+{
+    let releasing = legacy.project_status == ProjectStatus::Releasing;
+    let step = legacy.project_milestonestep.u128() as usize;
+    let released_amount = match legacy.project_status {
+        ProjectStatus::Done => legacy.backerbacked_amount + legacy.communitybacked_amount,
+        _ => released_principal(&legacy),
+    };
+
+    //-------old deposits never recorded aust, take it at today's rate, none without deposits----
+    let aust_amount = if let Some(rate) = rate {
+        let backed = legacy.backerbacked_amount + legacy.communitybacked_amount;
+        let rest = backed.checked_sub(released_principal(&legacy))?;
+        for backer in legacy.backer_states.iter_mut().chain(legacy.communitybacker_states.iter_mut()) {
+            backer.aust_amount.amount = ust_to_shares(backer.ust_amount.amount, rate)?;
+        }
+        ust_to_shares(rest, rate)?
+    } else {
+        Uint128::zero()
+    };
+
+    let mut backer_aust = Uint128::zero();
+    for backer in legacy.backer_states.iter().chain(legacy.communitybacker_states.iter()) {
+        backer_aust += backer.aust_amount.amount;
+    }
+
+    //-------one weighted vote per backer wallet, old yes votes carried over and tallied------
+    let mut weights: Vec<(Addr, Uint128)> = Vec::new();
+    for backer in legacy.backer_states.iter() {
+        match weights.iter_mut().find(|(wallet, _)| wallet == &backer.backer_wallet) {
+            Some((_, weight)) => *weight += backer.ust_amount.amount,
+            None => weights.push((backer.backer_wallet.clone(), backer.ust_amount.amount)),
+        }
+    }
+
+    let now = Uint128::from(env.block.time.seconds());
+    let milestone_states = legacy.milestone_states.into_iter().enumerate()
+        .map(|(i, milestone)| {
+            let milestone_votes: Vec<Vote> = if releasing && i >= step {
+                weights.iter()
+                    .filter(|(wallet, _)| milestone.milestone_votes.iter()
+                        .any(|vote| &vote.wallet == wallet && vote.voted))
+                    .map(|(wallet, weight)| Vote{
+                        wallet: wallet.clone(),
+                        weight: *weight,
+                        vote: Some(VoteOption::Yes),
+                        submitted_by: Some(wallet.clone()),
+                        submitted_at: None,
+                        round: Uint128::zero(),
+                    }).collect()
+            } else {
+                Vec::new()
+            };
+            let milestone_yes = milestone_votes.iter().map(|vote| vote.weight).sum();
+            let open = releasing && i == step && milestone.milestone_status == MILESTONE_VOTING;
+            Milestone{
+                milestone_step: milestone.milestone_step,
+                milestone_name: milestone.milestone_name,
+                milestone_description: milestone.milestone_description,
+                milestone_startdate: milestone.milestone_startdate,
+                milestone_enddate: milestone.milestone_enddate,
+                milestone_amount: milestone.milestone_amount,
+                milestone_status: milestone.milestone_status,
+                milestone_votes,
+                milestone_votestart: if open { Some(now) } else { None },
+                milestone_voteend: if open { Some(now + DEFAULT_MILESTONE_VOTEPERIOD) } else { None },
+                milestone_resubmitted: false,
+                milestone_round: Uint128::zero(),
+                milestone_yes,
+                milestone_no: Uint128::zero(),
+                milestone_abstain: Uint128::zero(),
+            }
+        })
+        .collect();
+
+    Ok(ProjectState{
+        project_company: legacy.project_company,
+        project_title: legacy.project_title,
+        project_description: legacy.project_description,
+        project_ecosystem: legacy.project_ecosystem,
+        project_createddate: legacy.project_createddate,
+        project_saft: legacy.project_saft,
+        project_logo: legacy.project_logo,
+        project_whitepaper: legacy.project_whitepaper,
+        project_website: legacy.project_website,
+        project_email: legacy.project_email,
+        project_id: legacy.project_id,
+        creator_wallet: legacy.creator_wallet,
+        project_collected: legacy.project_collected,
+        project_status: legacy.project_status,
+        fundraising_stage: legacy.fundraising_stage,
+        backerbacked_amount: legacy.backerbacked_amount,
+        communitybacked_amount: legacy.communitybacked_amount,
+        aust_amount,
+        backer_aust,
+        yield_claimed: Uint128::zero(),
+        yield_strategy: strategy,
+        redemption_shortfall: Uint128::zero(),
+        released_amount,
+        fee_schedule: None,
+        backer_states: legacy.backer_states,
+        communitybacker_states: legacy.communitybacker_states,
+        milestone_states,
+        project_milestonestep: legacy.project_milestonestep,
+        milestone_quorum: DEFAULT_MILESTONE_QUORUM,
+        milestone_threshold: DEFAULT_MILESTONE_THRESHOLD,
+        milestone_voteperiod: DEFAULT_MILESTONE_VOTEPERIOD,
+        rejection_policy: RejectionPolicy::Resubmit,
+        teammember_states: legacy.teammember_states,
+        vesting: legacy.vesting,
+        token_addr: legacy.token_addr,
+    })
+}
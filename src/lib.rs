@@ -5,11 +5,14 @@ pub mod msg;
 pub mod state;
 pub mod market;
 pub mod vesting;
+pub mod vault;
+pub mod strategy;
+pub mod legacy;
 
 pub use crate::error::ContractError;
 
 #[cfg(test)]
 mod testing;
 
-// #[cfg(test)]
-// mod mock_querier;
+#[cfg(test)]
+mod mock_querier;
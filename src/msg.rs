@@ -1,7 +1,9 @@
-use cosmwasm_std::{Uint128};
+use cosmwasm_std::{Addr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::state::{Milestone, TeamMember, VestingParameter};
+use crate::state::{Milestone, TeamMember, VestingParameter, VoteOption, RejectionPolicy, Role, PauseOperation,
+    ProjectStatus, YieldSplit, FeeSchedule, ReferralStats};
+use crate::strategy::YieldStrategy;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -9,15 +11,47 @@ pub struct InstantiateMsg {
     pub wefund: Option<String>,
     pub anchor_market: Option<String>,
     pub aust_token: Option<String>,
-    pub vesting_contract: Option<String>
+    pub vesting_contract: Option<String>,
+    pub community_manager: Option<String>,
+    pub timelock_period: Option<Uint128>,
+    pub yield_split: Option<YieldSplit>,
+    pub yield_strategy: Option<YieldStrategy>,
+    pub fee_schedule: Option<FeeSchedule>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    pub limit: Option<u32>, //projects converted in the migrate call, the rest with MigrateProjects
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
-    SetConfig { admin:Option<String>,  wefund: Option<String>, 
-        anchor_market: Option<String>, aust_token:Option<String> , 
-        vesting_contract:Option<String>},
+    /// roles: owner, config manager
+    SetConfig { wefund: Option<String>, community_manager:Option<String>, yield_split: Option<YieldSplit>,
+        referral_share: Option<Uint128> },
+    /// roles: owner, guardian
+    Pause { operations: Vec<PauseOperation> },
+    /// roles: owner
+    Unpause { operations: Vec<PauseOperation> },
+    /// roles: owner, config manager
+    ProposeConfigChange { anchor_market: Option<String>, aust_token: Option<String>,
+        vesting_contract: Option<String>, timelock_period: Option<Uint128>,
+        yield_strategy: Option<YieldStrategy> },
+    /// roles: owner, config manager, after the timelock
+    ExecuteConfigChange { id: Uint128 },
+    /// roles: owner
+    CancelConfigChange { id: Uint128 },
+    /// roles: owner, continues the project migration started by migrate, nothing else runs until it is done
+    MigrateProjects { limit: Option<u32> },
+    /// roles: owner
+    ProposeNewOwner { new_owner: String, expires_in: Uint128 },
+    /// roles: proposed owner, before expiry
+    AcceptOwnership {},
+    /// roles: owner
+    CancelOwnershipProposal {},
+    /// roles: anyone, project waits for WefundApprove
     AddProject { 
         project_company: String,
         project_title: String,
@@ -34,40 +68,151 @@ pub enum ExecuteMsg {
         project_milestones: Vec<Milestone>,
         project_teammembers: Vec<TeamMember>,
         vesting: Vec<VestingParameter>,
-        token_addr: String
+        token_addr: String,
+        milestone_quorum: Option<Uint128>,
+        milestone_threshold: Option<Uint128>,
+        milestone_voteperiod: Option<Uint128>,
+        rejection_policy: Option<RejectionPolicy>,
     },
+    /// roles: owner
     RemoveProject{project_id: Uint128 },
+    /// roles: owner, operator, before the project is approved
+    SetProjectStrategy{project_id: Uint128, strategy: YieldStrategy},
+    /// roles: owner
+    SetFeeSchedule{fee_schedule: FeeSchedule},
+    /// roles: owner, None goes back to the config schedule
+    SetProjectFeeSchedule{project_id: Uint128, fee_schedule: Option<FeeSchedule>},
 
+    /// roles: anyone, tokens are allocated at the price of the project's fundraising stage
     Back2Project { project_id: Uint128, backer_wallet: String, 
-        fundraising_stage: Uint128, token_amount: Uint128, 
-        otherchain:String, otherchain_wallet:String},
+        otherchain:String, otherchain_wallet:String, referrer: Option<String>},
 
+    /// roles: owner, operator, once the current milestone passed its vote, releases every remaining one
     CompleteProject{ project_id: Uint128 },
+    /// roles: owner, operator, while fundraising or releasing
     FailProject{project_id: Uint128 },
 
+    /// roles: owner, treasurer, sends the contract fees kept from backings and aust left from before the upgrade
     TransferAllCoins{wallet: String},
 
+    /// roles: owner, community manager
     AddCommunitymember{wallet: String},
+    /// roles: owner, community manager
     RemoveCommunitymember{wallet: String},
 
+    /// roles: owner, operator, project approver
     WefundApprove{project_id:Uint128},
+    /// roles: owner, only forward to an existing stage, stages also move on when sold out or past stage_end
     SetFundraisingStage{project_id: Uint128, stage: Uint128},
     
-    SetMilestoneVote{project_id: Uint128, wallet:String, voted: bool},
+    /// roles: backer of the vote or its delegate
+    SetMilestoneVote{project_id: Uint128, wallet:String, vote: VoteOption},
+    /// roles: backer
+    SetVoteDelegate{project_id: Uint128, delegate: Option<String>},
+    /// roles: anyone, after the voting window, not while voting is paused
+    CloseMilestoneVote{project_id: Uint128},
+    /// roles: owner, creator, not while voting is paused
+    ResubmitMilestone{project_id: Uint128},
 
+    /// roles: owner, operator, creator, the last milestone releases the rest of the backed ust
     ReleaseMilestone{project_id: Uint128},
 
-    SetProjectStatus{project_id: Uint128, status: Uint128}
+    /// roles: backer, community backer
+    ClaimRefund{project_id: Uint128},
+    /// roles: backer, community backer, creator, wefund
+    ClaimYield{project_id: Uint128},
+    /// roles: anyone with accrued fees, fee recipients and referrers
+    ClaimFees{},
+
+    /// roles: owner
+    SetProjectStatus{project_id: Uint128, status: Uint128},
+
+    /// roles: owner
+    GrantRole{address: String, role: Role},
+    /// roles: owner
+    RevokeRole{address: String, role: Role},
+    /// roles: holder of the granted role
+    RenounceRole{role: Role},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetConfig{},
-    GetAllProject{},
+    GetAllProject{
+        start_after: Option<Uint128>,
+        limit: Option<u32>,
+        status: Option<ProjectStatus>,
+        creator: Option<String>,
+        ecosystem: Option<String>,
+        under_cap: Option<bool>,
+    },
     GetProject { project_id:Uint128 },
     GetBacker{ project_id:Uint128},
     GetBalance{ wallet:String },
     GetCommunitymembers{},
+    GetClaimableRefund{ project_id:Uint128, wallet:String },
+    GetAccruedYield{ project_id:Uint128, wallet:String },
+    GetFeeSchedule{ project_id:Option<Uint128> },
+    GetAccruedFees{ wallet:String },
+    GetReferralStats{ referrer:String },
+    GetStageSales{ project_id:Uint128 },
+    GetFundraisingStage{ project_id:Uint128 },
+    GetRoles{ address:String },
+    GetPendingConfigChanges{},
+    GetPauseState{},
+    GetLedger{},
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProjectSummary {
+    pub project_id: Uint128,
+    pub project_company: String,
+    pub project_title: String,
+    pub project_ecosystem: String,
+    pub project_logo: String,
+    pub creator_wallet: Addr,
+    pub project_status: ProjectStatus,
+    pub project_collected: Uint128,
+    pub backerbacked_amount: Uint128,
+    pub communitybacked_amount: Uint128,
+    pub fundraising_stage: Uint128,
+    pub project_milestonestep: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllProjectResponse {
+    pub projects: Vec<ProjectSummary>,
+    pub next_key: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProjectReferrals {
+    pub project_id: Uint128,
+    pub stats: ReferralStats,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralStatsResponse {
+    pub referrer: Addr,
+    pub total: ReferralStats,
+    pub projects: Vec<ProjectReferrals>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageSale {
+    pub stage: Uint128,
+    pub stage_price: Uint128,
+    pub stage_amount: Uint128, //tokens
+    pub sold: Uint128, //smallest token unit
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FundraisingStageResponse {
+    pub open: bool, //false once every stage is sold out or closed
+    pub stage: Uint128,
+    pub stage_price: Uint128,
+    pub remaining: Uint128, //smallest token unit
+    pub stage_end: Option<Uint128>,
+}
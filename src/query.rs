@@ -1,14 +1,24 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Addr, to_binary, Binary, Deps, Env, StdResult,
+    Addr, to_binary, Binary, Deps, Env, StdError, StdResult,
     Uint128, QueryRequest, BankQuery,
     Coin, AllBalanceResponse
 };
 use cw20::{Cw20QueryMsg, BalanceResponse as Cw20BalanceResponse};
 
-use crate::msg::{QueryMsg};
-use crate::state::{Config, CONFIG, PROJECTSTATES, ProjectState, BackerState, COMMUNITY};
+use cw_storage_plus::Bound;
+use std::convert::TryInto;
+
+use crate::msg::{QueryMsg, ProjectSummary, AllProjectResponse, ProjectReferrals, ReferralStatsResponse,
+    StageSale, FundraisingStageResponse};
+use crate::state::{Config, CONFIG, projectstates, ProjectState, ProjectStatus, BackerState, status_key,
+    load_backers, load_projectstate, COMMUNITY,
+    Role, load_roles, ConfigChange, CONFIG_CHANGES, PauseState, PAUSE_STATE, Ledger, LEDGER,
+    FeeSchedule, ACCRUED_FEES, REFERRALS, REFERRAL_TOTALS, TOKENS_SOLD};
+use crate::contract::{calc_refund_claimable, calc_yield_claimable, query_exchange_rate, open_stage, token_unit,
+    needs_backing};
+use crate::strategy::shares_to_ust;
 
 // version info for migration info
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -16,13 +26,119 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetBalance{ wallet } => to_binary(&query_balance(deps, _env, wallet)?),
         QueryMsg::GetConfig{ } => to_binary(&query_getconfig(deps)?),
-        QueryMsg::GetAllProject{ } => to_binary(&query_allproject(deps)?),
+        QueryMsg::GetAllProject{ start_after, limit, status, creator, ecosystem, under_cap } => 
+            to_binary(&query_allproject(deps, start_after, limit, status, creator, ecosystem, under_cap)?),
         QueryMsg::GetProject{ project_id } => to_binary(&query_project(deps, project_id)?),
         QueryMsg::GetBacker{ project_id } => to_binary(&query_backer(deps, project_id)?),
         QueryMsg::GetCommunitymembers{ } => to_binary(&query_communitymembers(deps)?),
+        QueryMsg::GetClaimableRefund{ project_id, wallet } => 
+            to_binary(&query_claimablerefund(deps, project_id, wallet)?),
+        QueryMsg::GetAccruedYield{ project_id, wallet } => 
+            to_binary(&query_accruedyield(deps, project_id, wallet)?),
+        QueryMsg::GetFeeSchedule{ project_id } => to_binary(&query_feeschedule(deps, project_id)?),
+        QueryMsg::GetAccruedFees{ wallet } => to_binary(&query_accruedfees(deps, wallet)?),
+        QueryMsg::GetReferralStats{ referrer } => to_binary(&query_referralstats(deps, referrer)?),
+        QueryMsg::GetStageSales{ project_id } => to_binary(&query_stagesales(deps, project_id)?),
+        QueryMsg::GetFundraisingStage{ project_id } =>
+            to_binary(&query_fundraisingstage(deps, _env, project_id)?),
+        QueryMsg::GetRoles{ address } => to_binary(&query_roles(deps, address)?),
+        QueryMsg::GetPendingConfigChanges{ } => to_binary(&query_pendingconfigchanges(deps)?),
+        QueryMsg::GetPauseState{ } => to_binary(&query_pausestate(deps)?),
+        QueryMsg::GetLedger{ } => to_binary(&query_ledger(deps)?),
     }
 }
 
+fn query_claimablerefund(deps:Deps, id:Uint128, wallet:String) -> StdResult<Uint128>{
+    let x = projectstates().load(deps.storage, id.u128().into())?;
+    let wallet = deps.api.addr_validate(&wallet)?;
+    calc_refund_claimable(deps.storage, &x, &wallet)
+}
+fn query_accruedyield(deps:Deps, id:Uint128, wallet:String) -> StdResult<Uint128>{
+    let x = projectstates().load(deps.storage, id.u128().into())?;
+    let wallet = deps.api.addr_validate(&wallet)?;
+    let rate = query_exchange_rate(deps, &x)?;
+    let aust_amount = calc_yield_claimable(deps.storage, &x, &wallet, rate)?;
+    shares_to_ust(aust_amount, rate)
+}
+//------------the project's override, or the config schedule--------------
+fn query_feeschedule(deps:Deps, project_id:Option<Uint128>) -> StdResult<FeeSchedule>{
+    if let Some(id) = project_id {
+        let x = projectstates().load(deps.storage, id.u128().into())?;
+        if let Some(fee_schedule) = x.fee_schedule {
+            return Ok(fee_schedule);
+        }
+    }
+    Ok(CONFIG.load(deps.storage)?.fee_schedule)
+}
+fn query_accruedfees(deps:Deps, wallet:String) -> StdResult<Uint128>{
+    let wallet = deps.api.addr_validate(&wallet)?;
+    Ok(ACCRUED_FEES.may_load(deps.storage, &wallet)?.unwrap_or_default())
+}
+fn query_referralstats(deps:Deps, referrer:String) -> StdResult<ReferralStatsResponse>{
+    let referrer = deps.api.addr_validate(&referrer)?;
+    let total = REFERRAL_TOTALS.may_load(deps.storage, &referrer)?.unwrap_or_default();
+    let projects = REFERRALS.prefix(&referrer)
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| {
+            let (key, stats) = item?;
+            let id: [u8; 16] = key.as_slice().try_into()
+                .map_err(|_| StdError::generic_err("Invalid project key"))?;
+            Ok(ProjectReferrals{ project_id: Uint128::new(u128::from_be_bytes(id)), stats })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(ReferralStatsResponse{ referrer, total, projects })
+}
+fn query_stagesales(deps:Deps, id:Uint128) -> StdResult<Vec<StageSale>>{
+    let x = projectstates().load(deps.storage, id.u128().into())?;
+    x.vesting.iter().enumerate().map(|(stage, param)| {
+        let sold = TOKENS_SOLD.may_load(deps.storage, (id.u128().into(), (stage as u128).into()))?;
+        Ok(StageSale{
+            stage: Uint128::new(stage as u128),
+            stage_price: param.stage_price,
+            stage_amount: param.stage_amount,
+            sold: sold.unwrap_or_default(),
+        })
+    }).collect()
+}
+//------------the stage a backing would buy from now---------------
+fn query_fundraisingstage(deps:Deps, env:Env, id:Uint128) -> StdResult<FundraisingStageResponse>{
+    let x = projectstates().load(deps.storage, id.u128().into())?;
+    let unit = token_unit(deps, &x)?;
+    let open = open_stage(deps.storage, &x, x.fundraising_stage.u128() as usize,
+        env.block.time.seconds(), unit)?;
+    Ok(match open {
+        Some((stage, remaining)) => FundraisingStageResponse{
+            open: true,
+            stage: Uint128::new(stage as u128),
+            stage_price: x.vesting[stage].stage_price,
+            remaining,
+            stage_end: x.vesting[stage].stage_end,
+        },
+        None => FundraisingStageResponse{
+            open: false,
+            stage: Uint128::new(x.vesting.len() as u128),
+            stage_price: Uint128::zero(),
+            remaining: Uint128::zero(),
+            stage_end: None,
+        },
+    })
+}
+fn query_ledger(deps:Deps) -> StdResult<Ledger>{
+    LEDGER.load(deps.storage)
+}
+fn query_pausestate(deps:Deps) -> StdResult<PauseState>{
+    PAUSE_STATE.load(deps.storage)
+}
+fn query_pendingconfigchanges(deps:Deps) -> StdResult<Vec<ConfigChange>>{
+    CONFIG_CHANGES
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| item.map(|(_, change)| change))
+        .collect()
+}
+fn query_roles(deps:Deps, address:String) -> StdResult<Vec<Role>>{
+    let address = deps.api.addr_validate(&address)?;
+    load_roles(deps.storage, &address)
+}
 fn query_communitymembers(deps:Deps) -> StdResult<Vec<Addr>>{
     let community = COMMUNITY.load(deps.storage).unwrap();
     Ok(community)
@@ -52,23 +168,73 @@ fn query_getconfig(deps:Deps) -> StdResult<Config> {
     let config = CONFIG.load(deps.storage).unwrap();
     Ok(config)
 }
-fn query_allproject(deps:Deps) -> StdResult<Vec<ProjectState>> {
-    let all: StdResult<Vec<_>> = PROJECTSTATES.range(deps.storage, None, None, 
-        cosmwasm_std::Order::Ascending).collect();
-    let all = all.unwrap();
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
-    let mut all_project:Vec<ProjectState> = Vec::new();
-    for x in all{
-        all_project.push(x.1);
+#[allow(clippy::too_many_arguments)]
+fn query_allproject(deps:Deps, start_after:Option<Uint128>, limit:Option<u32>,
+    status:Option<ProjectStatus>, creator:Option<String>, ecosystem:Option<String>,
+    under_cap:Option<bool>
+) -> StdResult<AllProjectResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|id| Bound::exclusive_int(id.u128()));
+    let creator = creator.map(|s| deps.api.addr_validate(&s)).transpose()?;
+
+    //-------walk the narrowest index available------------
+    let map = projectstates();
+    let order = cosmwasm_std::Order::Ascending;
+    let iter = if let Some(creator) = &creator {
+        map.idx.creator.prefix(creator.as_bytes().to_vec()).range(deps.storage, start, None, order)
+    } else if let Some(ecosystem) = &ecosystem {
+        map.idx.ecosystem.prefix(ecosystem.as_bytes().to_vec()).range(deps.storage, start, None, order)
+    } else if let Some(status) = &status {
+        map.idx.status.prefix(status_key(status)).range(deps.storage, start, None, order)
+    } else {
+        map.range(deps.storage, start, None, order)
+    };
+
+    let projects = iter
+        .filter(|item| match item {
+            Ok((_, x)) => {
+                //-------under cap while backers can still back it------
+                status.as_ref().map_or(true, |s| &x.project_status == s)
+                    && creator.as_ref().map_or(true, |c| &x.creator_wallet == c)
+                    && ecosystem.as_ref().map_or(true, |e| &x.project_ecosystem == e)
+                    && under_cap.map_or(true, |u| u == needs_backing(x, false))
+            }
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| item.map(|(_, x)| project_summary(x)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let next_key = if projects.len() == limit {
+        projects.last().map(|x| x.project_id)
+    } else {
+        None
+    };
+    Ok(AllProjectResponse{ projects, next_key })
+}
+fn project_summary(x: ProjectState) -> ProjectSummary {
+    ProjectSummary{
+        project_id: x.project_id,
+        project_company: x.project_company,
+        project_title: x.project_title,
+        project_ecosystem: x.project_ecosystem,
+        project_logo: x.project_logo,
+        creator_wallet: x.creator_wallet,
+        project_status: x.project_status,
+        project_collected: x.project_collected,
+        backerbacked_amount: x.backerbacked_amount,
+        communitybacked_amount: x.communitybacked_amount,
+        fundraising_stage: x.fundraising_stage,
+        project_milestonestep: x.project_milestonestep,
     }
-    Ok(all_project)
 }
 fn query_backer(deps:Deps, id:Uint128) -> StdResult<Vec<BackerState>>{
-    let x = PROJECTSTATES.load(deps.storage, id.u128().into())?;
-    Ok(x.backer_states)
+    projectstates().load(deps.storage, id.u128().into())?;
+    load_backers(deps.storage, id, false)
 }
 fn query_project(deps:Deps, id:Uint128) -> StdResult<ProjectState>{
-    let x = PROJECTSTATES.load(deps.storage, id.u128().into())?;
-    
-    Ok(x)
+    load_projectstate(deps.storage, id)
 }
@@ -1,8 +1,10 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128, Coin, StdResult, Storage};
-use cw_storage_plus::{Item, Map, U128Key};
+use cosmwasm_std::{Addr, Uint128, Coin, Order, StdError, StdResult, Storage};
+use cw_storage_plus::{Item, Map, U128Key, IndexedMap, MultiIndex, Index, IndexList};
+
+use crate::strategy::YieldStrategy;
 //------------Config---------------------------------------
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
@@ -11,10 +13,235 @@ pub struct Config {
     pub anchor_market: Addr,
     pub aust_token: Addr,
     pub vesting_contract: Addr,
+    pub community_manager: Addr,
+    pub pending_owner: Option<OwnershipProposal>,
+    pub timelock_period: Uint128, //seconds a config change waits before execution
+    #[serde(default)]
+    pub yield_split: YieldSplit,
+    #[serde(default)]
+    pub yield_strategy: YieldStrategy, //for new projects
+    #[serde(default)]
+    pub fee_schedule: FeeSchedule, //unless the project overrides it
+    #[serde(default)]
+    pub referral_share: Uint128, //% of wefund's fee paid to the referrer of a backing
+}
+
+//------------% of anchor yield for backers, creator and wefund, sums to 100------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct YieldSplit{
+    pub backers: Uint128,
+    pub creator: Uint128,
+    pub wefund: Uint128,
+}
+
+impl Default for YieldSplit{
+    fn default() -> Self {
+        YieldSplit{
+            backers: Uint128::new(50),
+            creator: Uint128::new(30),
+            wefund: Uint128::new(20),
+        }
+    }
+}
+
+//------------fees taken from each backing, the tier with the highest min_amount reached applies------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeSchedule{
+    pub tiers: Vec<FeeTier>, //ascending min_amount
+    pub min_contribution: Uint128, //uusd, smaller backings are refused
+    #[serde(default)]
+    pub recipients: Vec<FeeRecipient>, //wefund's part by weight, empty sends it all to config.wefund
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeRecipient{
+    pub address: Addr,
+    pub weight: Uint128, //%, weights sum to 100
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeTier{
+    pub min_amount: Uint128, //uusd
+    pub percent: Uint128, //on top of the backed amount, 5 backs 100 of every 105
+    pub flat_fee: Uint128, //uusd, added to the percent fee
+    pub contract_fee: Uint128, //uusd of the fee kept by the contract, wefund gets the rest
+}
+
+impl Default for FeeSchedule{
+    fn default() -> Self {
+        FeeSchedule{
+            tiers: vec![
+                FeeTier{
+                    min_amount: Uint128::zero(),
+                    percent: Uint128::zero(),
+                    flat_fee: Uint128::new(5000000),
+                    contract_fee: Uint128::new(4000000),
+                },
+                FeeTier{
+                    min_amount: Uint128::new(100000000),
+                    percent: Uint128::new(5),
+                    flat_fee: Uint128::zero(),
+                    contract_fee: Uint128::new(4000000),
+                },
+            ],
+            min_contribution: Uint128::new(6000000),
+            recipients: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipProposal{
+    pub owner: Addr,
+    pub expires: Uint128, //block time in seconds
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+pub const DEFAULT_TIMELOCK_PERIOD: Uint128 = Uint128::new(2 * 24 * 3600); //2 days
+
+//------------queued changes of integration addresses------------------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigChange{
+    pub id: Uint128,
+    pub anchor_market: Option<Addr>,
+    pub aust_token: Option<Addr>,
+    pub vesting_contract: Option<Addr>,
+    pub timelock_period: Option<Uint128>,
+    pub yield_strategy: Option<YieldStrategy>,
+    pub proposed_at: Uint128,
+    pub executable_at: Uint128,
+}
+
+pub const CONFIG_CHANGE_SEQ: Item<Uint128> = Item::new("config_change_seq");
+pub const CONFIG_CHANGES: Map<U128Key, ConfigChange> = Map::new("config_changes");
+
+//------------Ledger: ust principal in and out of anchor------------------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Ledger{
+    pub total_deposited: Uint128, //backed ust deposited to anchor
+    pub total_released: Uint128, //principal redeemed for milestones and completed projects
+    pub total_refunded: Uint128, //principal redeemed into refund pools of failed projects
+}
+
+impl Ledger{
+    //-----principal still deposited in anchor-----------
+    pub fn outstanding(&self) -> StdResult<Uint128> {
+        Ok(self.total_deposited
+            .checked_sub(self.total_released)?
+            .checked_sub(self.total_refunded)?)
+    }
+}
+
+pub const LEDGER: Item<Ledger> = Item::new("ledger");
+
+//------------DepositStable waiting for its reply to credit the minted aust-----
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingDeposit{
+    pub project_id: Uint128,
+    pub wallet: Addr,
+    pub community: bool,
+    pub ust_amount: Uint128,
+}
+
+pub const PENDING_DEPOSIT: Item<PendingDeposit> = Item::new("pending_deposit");
+
+//------------redemption waiting for its reply to pay out the ust that arrived----
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RedemptionPayout{
+    Send{ recipient: Addr }, //creator on release and completion, claimer of yield
+    RefundPool{}, //failed project, backers claim it later
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRedemption{
+    pub project_id: Uint128,
+    pub payout: RedemptionPayout,
+    pub expected: Uint128, //ust estimated from the exchange rate
+    pub balance_before: Uint128, //uusd of the contract before the redeem
+}
+
+pub const PENDING_REDEMPTION: Item<PendingRedemption> = Item::new("pending_redemption");
+
+//------------Roles---------------------------------------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role{
+    Owner, //config.owner
+    Operator, //config.wefund or granted, WeFund operator
+    CommunityManager, //config.community_manager or granted
+    ProjectApprover, //granted, WefundApprove
+    Treasurer, //granted, TransferAllCoins
+    ConfigManager, //granted, SetConfig
+    Creator, //creator_wallet of the project
+    Guardian, //granted, can pause operations
+}
+
+//------------granted roles: wallet -> roles--------------------------------
+pub const ROLES: Map<&Addr, Vec<Role>> = Map::new("roles");
+
+//------------roles held by config plus the granted ones-------------------
+pub fn load_roles(store: &dyn Storage, addr: &Addr) -> StdResult<Vec<Role>>
+{
+    let config = CONFIG.load(store)?;
+    let mut roles = Vec::new();
+    if addr == &config.owner {
+        roles.push(Role::Owner);
+    }
+    if addr == &config.wefund {
+        roles.push(Role::Operator);
+    }
+    if addr == &config.community_manager {
+        roles.push(Role::CommunityManager);
+    }
+    for role in ROLES.may_load(store, addr)?.unwrap_or_default() {
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    Ok(roles)
+}
+
+//------------Pause---------------------------------------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseOperation{
+    Backing, //Back2Project
+    Voting, //SetMilestoneVote
+    Releases, //ReleaseMilestone, CompleteProject
+    ProjectCreation, //AddProject
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PauseState{
+    pub backing: bool,
+    pub voting: bool,
+    pub releases: bool,
+    pub project_creation: bool,
+}
+
+impl PauseState{
+    pub fn is_paused(&self, op: &PauseOperation) -> bool {
+        match op {
+            PauseOperation::Backing => self.backing,
+            PauseOperation::Voting => self.voting,
+            PauseOperation::Releases => self.releases,
+            PauseOperation::ProjectCreation => self.project_creation,
+        }
+    }
+    pub fn flag(&mut self, op: &PauseOperation) -> &mut bool {
+        match op {
+            PauseOperation::Backing => &mut self.backing,
+            PauseOperation::Voting => &mut self.voting,
+            PauseOperation::Releases => &mut self.releases,
+            PauseOperation::ProjectCreation => &mut self.project_creation,
+        }
+    }
+}
+
+pub const PAUSE_STATE: Item<PauseState> = Item::new("pause_state");
+
 //-------------backer states---------------------------------
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct BackerState{
@@ -25,10 +252,23 @@ pub struct BackerState{
     pub otherchain_wallet: String,
 }
 //--------------Vote---------------------------------------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteOption{
+    Yes,
+    No,
+    Abstain
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Vote{
     pub wallet: Addr,
-    pub voted: bool,
+    pub weight: Uint128, //backed ust amount of the wallet
+    pub vote: Option<VoteOption>, //None: not voted yet
+    pub submitted_by: Option<Addr>, //backer itself or its delegate
+    pub submitted_at: Option<Uint128>, //block time in seconds
+    #[serde(default)]
+    pub round: Uint128, //milestone_round it was cast in, older rounds don't count
 }
 
 //--------------Milestone---------------------------------------
@@ -40,8 +280,38 @@ pub struct Milestone{
     pub milestone_startdate: String,
     pub milestone_enddate: String,
     pub milestone_amount: Uint128,
-    pub milestone_status: Uint128, //0:voting, 1:releasing 2:released
+    pub milestone_status: Uint128, //0:voting, 1:approved 2:released 3:rejected
     pub milestone_votes: Vec<Vote>,
+    pub milestone_votestart: Option<Uint128>, //voting window, block time in seconds
+    pub milestone_voteend: Option<Uint128>,
+    #[serde(default)]
+    pub milestone_resubmitted: bool, //only one resubmission after rejection
+    #[serde(default)]
+    pub milestone_round: Uint128, //moves on with a resubmission
+    #[serde(default)]
+    pub milestone_yes: Uint128, //backed ust voted yes in this round
+    #[serde(default)]
+    pub milestone_no: Uint128,
+    #[serde(default)]
+    pub milestone_abstain: Uint128,
+}
+//--------------Milestone status-----------------------------------
+pub const MILESTONE_VOTING: Uint128 = Uint128::new(0);
+pub const MILESTONE_APPROVED: Uint128 = Uint128::new(1); //vote passed, waiting release
+pub const MILESTONE_RELEASED: Uint128 = Uint128::new(2);
+pub const MILESTONE_REJECTED: Uint128 = Uint128::new(3);
+
+//--------------default milestone vote rule, in percent-------------
+pub const DEFAULT_MILESTONE_QUORUM: Uint128 = Uint128::new(50);
+pub const DEFAULT_MILESTONE_THRESHOLD: Uint128 = Uint128::new(50);
+pub const DEFAULT_MILESTONE_VOTEPERIOD: Uint128 = Uint128::new(14 * 24 * 3600); //2 weeks
+
+//--------------what happens when a milestone is rejected-----------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionPolicy{
+    Resubmit, //creator can resubmit the milestone once, then fail
+    Fail, //fail the project and refund backers
 }
 //------------Team Description-------------------------------
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -54,11 +324,13 @@ pub struct TeamMember{
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct VestingParameter{
     pub stage_title: String,
-    pub stage_price: Uint128,
-    pub stage_amount: Uint128,
+    pub stage_price: Uint128, //uusd per token
+    pub stage_amount: Uint128, //tokens for sale in the stage
     pub stage_soon: Uint128,
     pub stage_after: Uint128,
-    pub stage_period: Uint128   
+    pub stage_period: Uint128,
+    #[serde(default)]
+    pub stage_end: Option<Uint128>, //block time in seconds, the sale moves on to the next stage
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -94,16 +366,34 @@ pub struct ProjectState{
 
     pub backerbacked_amount: Uint128,
     pub communitybacked_amount: Uint128,
-//---------backer states for 50% of collected------------------------    
+    #[serde(default)]
+    pub aust_amount: Uint128, //aust minted for this project and not redeemed yet
+    #[serde(default)]
+    pub backer_aust: Uint128, //aust minted for all backings, what backer shares divide
+    #[serde(default)]
+    pub yield_claimed: Uint128, //aust of yield already claimed
+    #[serde(default)]
+    pub yield_strategy: YieldStrategy, //fixed once backing starts
+    #[serde(default)]
+    pub redemption_shortfall: Uint128, //ust expected from redemptions but not received
+    #[serde(default)]
+    pub released_amount: Uint128, //backed ust already paid out to the creator
+    #[serde(default)]
+    pub fee_schedule: Option<FeeSchedule>, //overrides config.fee_schedule
+//---------backer states for 50% of collected, kept in BACKERS---------    
     pub backer_states: Vec<BackerState>,
 
-//---------community backer states for 50% of collected---------------
+//---------community backer states for 50% of collected, kept in COMMUNITY_BACKERS--
     pub communitybacker_states: Vec<BackerState>,
 
-//----------milestone states-----------------------------------------
+//----------milestone states, kept in MILESTONES and MILESTONE_VOTES------
     pub milestone_states: Vec<Milestone>,
     pub project_milestonestep: Uint128, 
-//---------team members-----------------------------------------------
+    pub milestone_quorum: Uint128, //% of backed amount that must vote
+    pub milestone_threshold: Uint128, //% of yes among yes+no to approve
+    pub milestone_voteperiod: Uint128, //seconds
+    pub rejection_policy: RejectionPolicy,
+//---------team members, kept in TEAM_MEMBERS---------------------------
     pub teammember_states: Vec<TeamMember>,
 //---------vesting-----------------------------------------------
     pub vesting: Vec<VestingParameter>,
@@ -111,7 +401,45 @@ pub struct ProjectState{
     pub token_addr: Addr,
 }
 pub const PROJECT_SEQ: Item<Uint128> = Item::new("prj_seq");
-pub const PROJECTSTATES: Map<U128Key, ProjectState> = Map::new("prj");
+
+//------------projects indexed by status, creator and ecosystem--------------
+pub struct ProjectIndexes<'a> {
+    pub status: MultiIndex<'a, (Vec<u8>, Vec<u8>), ProjectState>,
+    pub creator: MultiIndex<'a, (Vec<u8>, Vec<u8>), ProjectState>,
+    pub ecosystem: MultiIndex<'a, (Vec<u8>, Vec<u8>), ProjectState>,
+}
+
+impl<'a> IndexList<ProjectState> for ProjectIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<ProjectState>> + '_> {
+        let v: Vec<&dyn Index<ProjectState>> = vec![&self.status, &self.creator, &self.ecosystem];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn status_key(status: &ProjectStatus) -> Vec<u8> {
+    vec![status.clone() as u8]
+}
+
+pub fn projectstates<'a>() -> IndexedMap<'a, U128Key, ProjectState, ProjectIndexes<'a>> {
+    let indexes = ProjectIndexes {
+        status: MultiIndex::new(
+            |x, pk| (status_key(&x.project_status), pk),
+            "prj",
+            "prj__status",
+        ),
+        creator: MultiIndex::new(
+            |x, pk| (x.creator_wallet.as_bytes().to_vec(), pk),
+            "prj",
+            "prj__creator",
+        ),
+        ecosystem: MultiIndex::new(
+            |x, pk| (x.project_ecosystem.as_bytes().to_vec(), pk),
+            "prj",
+            "prj__ecosystem",
+        ),
+    };
+    IndexedMap::new("prj", indexes)
+}
 
 pub fn save_projectstate(store: &mut dyn Storage, _prj: &mut ProjectState) 
     -> StdResult<()> 
@@ -121,9 +449,200 @@ pub fn save_projectstate(store: &mut dyn Storage, _prj: &mut ProjectState)
     let id = id.checked_add(Uint128::new(1))?;
     PROJECT_SEQ.save(store, &id)?;
 
-    _prj.project_id = id.clone();
-    PROJECTSTATES.save(store, id.u128().into(), &_prj)
+    _prj.project_id = id;
+    store_projectstate(store, _prj)
+}
+
+//------------backers, milestones, votes and team members go to their own maps-------
+pub fn store_projectstate(store: &mut dyn Storage, _prj: &mut ProjectState)
+    -> StdResult<()>
+{
+    let id = _prj.project_id;
+    for backer in _prj.backer_states.drain(..) {
+        add_backer(store, id, backer, false)?;
+    }
+    for backer in _prj.communitybacker_states.drain(..) {
+        add_backer(store, id, backer, true)?;
+    }
+    for (step, mut milestone) in _prj.milestone_states.drain(..).enumerate() {
+        let key = milestone_key(id, Uint128::new(step as u128));
+        for vote in milestone.milestone_votes.drain(..) {
+            MILESTONE_VOTES.save(store, (key.0.clone(), key.1.clone(), &vote.wallet), &vote)?;
+        }
+        MILESTONES.save(store, key, &milestone)?;
+    }
+    for (i, member) in _prj.teammember_states.drain(..).enumerate() {
+        TEAM_MEMBERS.save(store, (id.u128().into(), (i as u128).into()), &member)?;
+    }
+
+    projectstates().save(store, id.u128().into(), _prj)
+}
+
+//------------normalized project parts--------------------------------------
+pub const BACKERS: Map<(U128Key, &Addr), BackerState> = Map::new("prj_backers");
+pub const COMMUNITY_BACKERS: Map<(U128Key, &Addr), BackerState> = Map::new("prj_communitybackers");
+pub const MILESTONES: Map<(U128Key, U128Key), Milestone> = Map::new("prj_milestones"); //(project_id, index), votes in MILESTONE_VOTES
+pub const MILESTONE_VOTES: Map<(U128Key, U128Key, &Addr), Vote> = Map::new("prj_milestone_votes");
+pub const TEAM_MEMBERS: Map<(U128Key, U128Key), TeamMember> = Map::new("prj_teammembers");
+
+pub fn milestone_key(project_id: Uint128, step: Uint128) -> (U128Key, U128Key) {
+    (project_id.u128().into(), step.u128().into())
+}
+
+//------------one entry per wallet, repeated backing adds up------------------
+pub fn add_backer(store: &mut dyn Storage, project_id: Uint128, backer: BackerState, community: bool)
+    -> StdResult<()>
+{
+    let map = if community { COMMUNITY_BACKERS } else { BACKERS };
+    let key = (U128Key::new(project_id.u128()), &backer.backer_wallet);
+    let state = match map.may_load(store, key.clone())? {
+        Some(mut state) => {
+            state.ust_amount.amount += backer.ust_amount.amount;
+            state.aust_amount.amount += backer.aust_amount.amount;
+            state.otherchain = backer.otherchain.clone();
+            state.otherchain_wallet = backer.otherchain_wallet.clone();
+            state
+        }
+        None => backer.clone(),
+    };
+    map.save(store, key, &state)
+}
+
+//------------aust minted for a backing, on the backer and on the project-------
+pub fn add_backer_aust(store: &mut dyn Storage, project_id: Uint128, wallet: &Addr, community: bool, amount: Uint128)
+    -> StdResult<()>
+{
+    let map = if community { COMMUNITY_BACKERS } else { BACKERS };
+    map.update(store, (U128Key::new(project_id.u128()), wallet), |backer| match backer {
+        Some(mut backer) => {
+            backer.aust_amount.amount += amount;
+            Ok(backer)
+        }
+        None => Err(StdError::not_found("BackerState")),
+    })?;
+    projectstates().update(store, project_id.u128().into(), |project| match project {
+        Some(mut project) => {
+            project.aust_amount += amount;
+            project.backer_aust += amount;
+            Ok(project)
+        }
+        None => Err(StdError::not_found("ProjectState")),
+    })?;
+    Ok(())
+}
+
+pub fn load_backers(store: &dyn Storage, project_id: Uint128, community: bool)
+    -> StdResult<Vec<BackerState>>
+{
+    let map = if community { COMMUNITY_BACKERS } else { BACKERS };
+    map.prefix(project_id.u128().into())
+        .range(store, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, backer)| backer))
+        .collect()
+}
+
+pub fn load_milestones(store: &dyn Storage, project_id: Uint128) -> StdResult<Vec<Milestone>>
+{
+    MILESTONES.prefix(project_id.u128().into())
+        .range(store, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, milestone)| milestone))
+        .collect()
+}
+
+pub fn load_votes(store: &dyn Storage, project_id: Uint128, step: Uint128) -> StdResult<Vec<Vote>>
+{
+    MILESTONE_VOTES.prefix((project_id.u128().into(), step.u128().into()))
+        .range(store, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, vote)| vote))
+        .collect()
+}
+
+//------------project with backers, milestones, votes and team members put back-----
+pub fn load_projectstate(store: &dyn Storage, project_id: Uint128) -> StdResult<ProjectState>
+{
+    let mut x = projectstates().load(store, project_id.u128().into())?;
+    x.backer_states = load_backers(store, project_id, false)?;
+    x.communitybacker_states = load_backers(store, project_id, true)?;
+    x.milestone_states = load_milestones(store, project_id)?;
+    for (step, milestone) in x.milestone_states.iter_mut().enumerate() {
+        milestone.milestone_votes = load_votes(store, project_id, Uint128::new(step as u128))?
+            .into_iter()
+            .filter(|vote| vote.round == milestone.milestone_round)
+            .collect();
+    }
+    x.teammember_states = TEAM_MEMBERS.prefix(project_id.u128().into())
+        .range(store, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, member)| member))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(x)
+}
+
+pub fn remove_projectstate(store: &mut dyn Storage, project_id: Uint128) -> StdResult<()>
+{
+    let x = load_projectstate(store, project_id)?;
+    let id = U128Key::new(project_id.u128());
+    for backer in x.backer_states.iter() {
+        BACKERS.remove(store, (id.clone(), &backer.backer_wallet));
+    }
+    for backer in x.communitybacker_states.iter() {
+        COMMUNITY_BACKERS.remove(store, (id.clone(), &backer.backer_wallet));
+    }
+    for step in 0..x.milestone_states.len() as u128 {
+        for vote in load_votes(store, project_id, Uint128::new(step))? {
+            MILESTONE_VOTES.remove(store, (id.clone(), step.into(), &vote.wallet));
+        }
+        MILESTONES.remove(store, (id.clone(), step.into()));
+    }
+    for i in 0..x.teammember_states.len() {
+        TEAM_MEMBERS.remove(store, (id.clone(), (i as u128).into()));
+    }
+    projectstates().remove(store, id)
+}
+
+//------------refunds of failed project-----------------------------------
+pub const REFUND_POOLS: Map<U128Key, Uint128> = Map::new("refund_pools"); //project_id -> ust to refund
+pub const REFUNDS_CLAIMED: Map<(U128Key, &Addr), Uint128> = Map::new("refunds_claimed");
+
+//------------aust of yield claimed per project and wallet-----------------
+pub const YIELD_CLAIMED: Map<(U128Key, &Addr), Uint128> = Map::new("yield_claimed");
+
+//------------fees not sent yet: the rounding remainder, and shares too small to send------
+pub const FEE_REMAINDER: Item<Uint128> = Item::new("fee_remainder");
+pub const CONTRACT_FEES: Item<Uint128> = Item::new("contract_fees"); //uusd kept per backing, for TransferAllCoins
+pub const CONTRACT_YIELD: Item<Uint128> = Item::new("contract_yield"); //aust no project owned at the upgrade
+pub const ACCRUED_FEES: Map<&Addr, Uint128> = Map::new("accrued_fees");
+pub const MIN_FEE_PAYOUT: Uint128 = Uint128::new(10000); //uusd, smaller shares wait in ACCRUED_FEES
+
+//------------backings brought in by a referrer: (referrer, project_id) and all projects------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReferralStats{
+    pub backings: Uint128, //number of backings
+    pub backed: Uint128, //ust backed, after fees
+    pub earned: Uint128, //ust of referral fees
 }
+pub const REFERRALS: Map<(&Addr, U128Key), ReferralStats> = Map::new("referrals");
+pub const REFERRAL_TOTALS: Map<&Addr, ReferralStats> = Map::new("referral_totals");
+
+pub fn add_referral(store: &mut dyn Storage, referrer: &Addr, project_id: Uint128, backed: Uint128,
+    earned: Uint128) -> StdResult<()>
+{
+    let add = |stats: Option<ReferralStats>| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.backings += Uint128::new(1);
+        stats.backed += backed;
+        stats.earned += earned;
+        Ok(stats)
+    };
+    REFERRALS.update(store, (referrer, project_id.u128().into()), add)?;
+    REFERRAL_TOTALS.update(store, referrer, add)?;
+    Ok(())
+}
+
+//------------tokens sold: (project_id, stage) -> smallest token unit, up to stage_amount---------
+pub const TOKENS_SOLD: Map<(U128Key, U128Key), Uint128> = Map::new("tokens_sold");
+
+//------------vote delegates: (project_id, backer) -> delegate---------------
+pub const VOTE_DELEGATES: Map<(U128Key, &Addr), Addr> = Map::new("vote_delegates");
 
 //------------community array------------------------------------------------
 pub const COMMUNITY: Item<Vec<Addr>> = Item::new("community");
\ No newline at end of file
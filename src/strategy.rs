@@ -0,0 +1,129 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, to_binary, Coin, CosmosMsg, Deps, OverflowError, OverflowOperation,
+    StdError, StdResult, Uint128, WasmMsg};
+use cosmwasm_bignumber::{Decimal256, Uint256};
+use cw20::Cw20ExecuteMsg;
+
+use crate::market::{ExecuteMsg as AnchorMarket, Cw20HookMsg as AnchorHook,
+    QueryMsg as AnchorQuery, EpochStateResponse};
+use crate::vault::{ExecuteMsg as VaultMsg, Cw20HookMsg as VaultHook,
+    QueryMsg as VaultQuery, ExchangeRateResponse};
+use crate::state::Config;
+
+//------------where backed ust waits for release, project aust_amount counts its shares------
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YieldStrategy{
+    Anchor, //config.anchor_market, shares are config.aust_token
+    Hold, //ust stays in the contract, one share per uusd, no yield
+    Vault{ vault: Addr, share_token: Addr },
+}
+
+impl Default for YieldStrategy{
+    fn default() -> Self {
+        YieldStrategy::Anchor
+    }
+}
+
+impl YieldStrategy{
+    //-----ust per share-------------------
+    pub fn exchange_rate(&self, deps: Deps, config: &Config) -> StdResult<Decimal256> {
+        match self {
+            YieldStrategy::Anchor => {
+                let epoch: EpochStateResponse = deps.querier.query_wasm_smart(
+                    config.anchor_market.to_string(),
+                    &AnchorQuery::EpochState{
+                        block_height: None,
+                        distributed_interest: None,
+                    }
+                )?;
+                Ok(epoch.exchange_rate)
+            }
+            YieldStrategy::Hold => Ok(Decimal256::one()),
+            YieldStrategy::Vault{ vault, .. } => {
+                let res: ExchangeRateResponse = deps.querier.query_wasm_smart(
+                    vault.to_string(),
+                    &VaultQuery::ExchangeRate{}
+                )?;
+                Ok(res.exchange_rate)
+            }
+        }
+    }
+
+    //-----None when the ust stays in the contract--------
+    pub fn deposit_msg(&self, config: &Config, fund: Coin) -> StdResult<Option<CosmosMsg>> {
+        let (contract_addr, msg) = match self {
+            YieldStrategy::Anchor => (config.anchor_market.to_string(), to_binary(&AnchorMarket::DepositStable{})?),
+            YieldStrategy::Hold => return Ok(None),
+            YieldStrategy::Vault{ vault, .. } => (vault.to_string(), to_binary(&VaultMsg::Deposit{})?),
+        };
+        Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute{
+            contract_addr,
+            msg,
+            funds: vec![fund],
+        })))
+    }
+
+    //-----send shares back for ust, None when there is nothing to redeem------
+    pub fn redeem_msg(&self, config: &Config, amount: Uint128) -> StdResult<Option<CosmosMsg>> {
+        if amount.is_zero() {
+            return Ok(None);
+        }
+        let (token, contract, msg) = match self {
+            YieldStrategy::Anchor => (config.aust_token.to_string(), config.anchor_market.to_string(),
+                to_binary(&AnchorHook::RedeemStable{})?),
+            YieldStrategy::Hold => return Ok(None),
+            YieldStrategy::Vault{ vault, share_token } => (share_token.to_string(), vault.to_string(),
+                to_binary(&VaultHook::Redeem{})?),
+        };
+        Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute{
+            contract_addr: token,
+            msg: to_binary(&Cw20ExecuteMsg::Send{ contract, msg, amount })?,
+            funds: Vec::new(),
+        })))
+    }
+}
+
+//------------share and ust conversions, every rounding leaves the remainder with the backers------
+//------------shares a ust amount buys at rate, rounded down: what a deposit mints, what a payout takes-----
+pub fn ust_to_shares(ust: Uint128, rate: Decimal256) -> StdResult<Uint128> {
+    let (quotient, _) = divide_by_rate(ust, rate)?;
+    to_uint128(quotient)
+}
+
+//------------shares a ust amount needs at rate, rounded up: principal kept back for the backers-----
+pub fn ust_to_shares_ceil(ust: Uint128, rate: Decimal256) -> StdResult<Uint128> {
+    let (quotient, exact) = divide_by_rate(ust, rate)?;
+    if exact {
+        to_uint128(quotient)
+    } else {
+        to_uint128(quotient + Uint256::one())
+    }
+}
+
+//------------ust the shares are worth at rate, rounded down------------
+pub fn shares_to_ust(shares: Uint128, rate: Decimal256) -> StdResult<Uint128> {
+    let (atomics, overflow) = Uint256::from(shares).0.overflowing_mul(rate.0);
+    if overflow {
+        return Err(StdError::overflow(OverflowError::new(OverflowOperation::Mul, shares, rate)));
+    }
+    to_uint128(Uint256(atomics / Decimal256::DECIMAL_FRACTIONAL))
+}
+
+//------------ust / rate, and whether it divided evenly; ust * 10^18 can't overflow 256 bits-----
+fn divide_by_rate(ust: Uint128, rate: Decimal256) -> StdResult<(Uint256, bool)> {
+    if rate.is_zero() {
+        return Err(StdError::generic_err("Exchange rate is zero"));
+    }
+    let atomics = Uint256::from(ust).0 * Decimal256::DECIMAL_FRACTIONAL;
+    Ok((Uint256(atomics / rate.0), (atomics % rate.0).is_zero()))
+}
+
+fn to_uint128(value: Uint256) -> StdResult<Uint128> {
+    if value > Uint256::from(u128::MAX) {
+        return Err(StdError::generic_err(format!("{} does not fit in Uint128", value)));
+    }
+    Ok(value.into())
+}
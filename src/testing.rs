@@ -1,29 +1,49 @@
-use super::*;
-use cosmwasm_std::{from_binary, Addr, CosmosMsg, WasmMsg,
-    BankQuery, BalanceResponse, AllBalanceResponse, Coin, Uint128};
-use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR, mock_dependencies};
+use cosmwasm_std::{from_binary, to_binary, Addr, BankMsg, Coin, CosmosMsg, Uint128, Deps, DepsMut, WasmMsg, OwnedDeps,
+    Response, Reply, ContractResult, SubMsgExecutionResponse, Event, Env, StdError};
+use cosmwasm_bignumber::Decimal256;
+use std::str::FromStr;
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
 
-use crate::contract::{execute, instantiate};
+use crate::contract::{execute, instantiate, migrate, reply, calc_milestone_status, DEPOSIT_REPLY_ID,
+    REDEEM_REPLY_ID};
+use crate::legacy::{LegacyConfig, LegacyMilestone, LegacyProjectState, LegacyVote};
+use cw_storage_plus::{Item, Map, U128Key};
+use cw2::set_contract_version;
 use crate::query::{query};
-use crate::state::{Milestone, Config, ProjectState};
-use crate::msg::{QueryMsg, ExecuteMsg, InstantiateMsg};
-
-// use crate::mock_querier::mock_dependencies;
+use crate::state::{projectstates, status_key, load_backers, load_milestones, load_votes,
+    BackerState, TeamMember, PROJECT_SEQ, Ledger, YieldSplit, FeeSchedule, FeeTier, FeeRecipient, ReferralStats, VestingParameter, Config, ConfigChange, PauseOperation, PauseState, Milestone, ProjectState, ProjectStatus, VoteOption, RejectionPolicy, Role,
+    MILESTONE_VOTING, MILESTONE_APPROVED, MILESTONE_RELEASED, MILESTONE_REJECTED};
+use crate::msg::{AllProjectResponse, MigrateMsg, QueryMsg, ExecuteMsg, InstantiateMsg, ProjectReferrals,
+    ReferralStatsResponse, StageSale, FundraisingStageResponse};
+use crate::strategy::{YieldStrategy, ust_to_shares, ust_to_shares_ceil, shares_to_ust};
+use crate::vault::Cw20HookMsg as VaultHook;
+use crate::vesting::ExecuteMsg as VestingMsg;
 use cw20::Cw20ExecuteMsg;
+use crate::ContractError;
+
+use crate::mock_querier::{mock_dependencies, WasmMockQuerier};
 // use terraswap::asset::{Asset, AssetInfo};
 // use terraswap::pair::ExecuteMsg as TerraswapExecuteMsg;
 
 #[test]
 fn workflow(){
     let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(500000000))],
+    )]);
     
     let msg = InstantiateMsg{
         admin: Some(String::from("admin")),
         wefund: Some(String::from("Wefund")),
         anchor_market: Some( "market".to_string()),
         aust_token: Some("aust".to_string()),
-        fundraising_contract: Some("fundrasing".to_string()),
-        vesting_contract: Some("vesting".to_string())
+        vesting_contract: Some("vesting".to_string()),
+        community_manager: None,
+        timelock_period: None,
+        yield_split: None,
+        yield_strategy: None,
+        fee_schedule: None,
     };
 //instantiate
     let info = mock_info("admin", &[]);
@@ -55,7 +75,14 @@ fn workflow(){
         milestone_enddate: String::from("enddate"),
         milestone_amount: Uint128::new(100),
         milestone_status: Uint128::new(0),
-        milestone_votes: Vec::new()
+        milestone_votes: Vec::new(),
+        milestone_votestart: None,
+        milestone_voteend: None,
+        milestone_resubmitted: false,
+        milestone_round: Uint128::zero(),
+        milestone_yes: Uint128::zero(),
+        milestone_no: Uint128::zero(),
+        milestone_abstain: Uint128::zero(),
     };
     let milestone2 = Milestone{
         milestone_step: Uint128::new(1),
@@ -65,7 +92,14 @@ fn workflow(){
         milestone_enddate: String::from("enddate"),
         milestone_amount: Uint128::new(200),
         milestone_status: Uint128::new(0),
-        milestone_votes: Vec::new()
+        milestone_votes: Vec::new(),
+        milestone_votestart: None,
+        milestone_voteend: None,
+        milestone_resubmitted: false,
+        milestone_round: Uint128::zero(),
+        milestone_yes: Uint128::zero(),
+        milestone_no: Uint128::zero(),
+        milestone_abstain: Uint128::zero(),
     };
     let milestone_states = vec![milestone1, milestone2];
     let msg = ExecuteMsg::AddProject{
@@ -85,6 +119,10 @@ fn workflow(){
         project_teammembers: Vec::new(),
         vesting: Vec::new(),
         token_addr: "token1".to_string(),
+        milestone_quorum: None,
+        milestone_threshold: None,
+        milestone_voteperiod: None,
+        rejection_policy: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
     // assert_eq!(res.messages.len(), 0);
@@ -117,7 +155,6 @@ fn workflow(){
         let info = mock_info("admin", &[]);
         let msg = ExecuteMsg::WefundApprove{
             project_id: Uint128::new(1),
-            deadline: Uint128::from(mock_env().block.time.seconds())
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("WeFund Approve: {:?}", res);
@@ -130,14 +167,14 @@ fn workflow(){
         // let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         // println!("WeFund Approve: {:?}", res);
 // //Set community vote
-        let info = mock_info("community1", &[]);
-        let msg = ExecuteMsg::SetCommunityVote{
-            project_id: Uint128::new(1),
-            wallet: String::from("community1"),
-            voted: true
-        };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-        println!("Set Community vote: {:?}", res);
+        // let info = mock_info("community1", &[]);
+        // let msg = ExecuteMsg::SetCommunityVote{
+        //     project_id: Uint128::new(1),
+        //     wallet: String::from("community1"),
+        //     voted: true
+        // };
+        // let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        // println!("Set Community vote: {:?}", res);
 
         // let info = mock_info("community2", &[]);
         // let msg = ExecuteMsg::SetCommunityVote{
@@ -154,11 +191,11 @@ fn workflow(){
             backer_wallet: String::from("backer1"),
             otherchain: "ethereum".to_string(),
             otherchain_wallet: "ether_wallet".to_string(),
-            fundraising_stage: "seed".to_string(),
-            token_amount: Uint128::new(10)
+            referrer: None,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("back2project:{:?}", res);
+        mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
 
         let info = mock_info("backer2", &[Coin::new(210000000, "uusd")]);
         let msg = ExecuteMsg::Back2Project{
@@ -166,11 +203,11 @@ fn workflow(){
             backer_wallet: String::from("backer2"),
             otherchain: "ethereum".to_string(),
             otherchain_wallet: "ether_wallet".to_string(),
-            fundraising_stage: "seed".to_string(),
-            token_amount: Uint128::new(10)
+            referrer: None,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("back2project:{:?}", res);
+        mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
 
         let info = mock_info("community1", &[Coin::new(210000000, "uusd")]);
         let msg = ExecuteMsg::Back2Project{
@@ -178,11 +215,11 @@ fn workflow(){
             backer_wallet: String::from("community1"),
             otherchain: "ethereum".to_string(),
             otherchain_wallet: "ether_wallet".to_string(),
-            fundraising_stage: "presale".to_string(),
-            token_amount: Uint128::new(10)
+            referrer: None,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("back2project:{:?}", res);
+        mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
 // //-Get Project-----------------
 // let msg = QueryMsg::GetAllProject{};
 // let allproject = query(deps.as_ref(), mock_env(), msg).unwrap();
@@ -194,7 +231,7 @@ fn workflow(){
         let msg = ExecuteMsg::SetMilestoneVote{
             project_id: Uint128::new(1),
             wallet: String::from("backer1"),
-            voted: true,
+            vote: VoteOption::Yes,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("set milestone vote:{:?}", res);
@@ -203,16 +240,23 @@ fn workflow(){
         let msg = ExecuteMsg::SetMilestoneVote{
             project_id: Uint128::new(1),
             wallet: String::from("backer2"),
-            voted: true,
+            vote: VoteOption::Yes,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("set milestone vote:{:?}", res);
+// //release milestone
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(res.attributes.iter().any(|attr| attr.key == "milestone_step" && attr.value == "0"));
+        assert_eq!(load_project(deps.as_ref(), 1).project_status, ProjectStatus::Releasing);
 
         let info = mock_info("backer1", &[]);
         let msg = ExecuteMsg::SetMilestoneVote{
             project_id: Uint128::new(1),
             wallet: String::from("backer1"),
-            voted: true,
+            vote: VoteOption::Yes,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("set milestone vote:{:?}", res);
@@ -221,10 +265,17 @@ fn workflow(){
         let msg = ExecuteMsg::SetMilestoneVote{
             project_id: Uint128::new(1),
             wallet: String::from("backer2"),
-            voted: true,
+            vote: VoteOption::Yes,
         };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         println!("set milestone vote:{:?}", res);
+
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(res.attributes.iter().any(|attr| attr.key == "milestone_step" && attr.value == "1"));
+        assert_eq!(load_project(deps.as_ref(), 1).project_status, ProjectStatus::Done);
         
 // //-Get Project-----------------
 //     let msg = QueryMsg::GetAllProject{};
@@ -261,10 +312,1757 @@ fn workflow(){
     // let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
 //-Get Project-----------------
-    let msg = QueryMsg::GetAllProject{};
+    let msg = QueryMsg::GetAllProject{ start_after: None, limit: None, status: None,
+        creator: None, ecosystem: None, under_cap: None };
     let allproject = query(deps.as_ref(), mock_env(), msg).unwrap();
 
-    let res:Vec<ProjectState> = from_binary(&allproject).unwrap();
+    let res:AllProjectResponse = from_binary(&allproject).unwrap();
     println!("allproject {:?}", res );
+    assert_eq!(res.projects[0].project_status, ProjectStatus::Done);
+}
+
+fn mock_milestone(step: u128, amount: u128) -> Milestone {
+    Milestone{
+        milestone_step: Uint128::new(step),
+        milestone_name: format!("milestone{}", step + 1),
+        milestone_description: format!("milestone{}", step + 1),
+        milestone_startdate: String::from("startdate"),
+        milestone_enddate: String::from("enddate"),
+        milestone_amount: Uint128::new(amount),
+        milestone_status: Uint128::new(0),
+        milestone_votes: Vec::new(),
+        milestone_votestart: None,
+        milestone_voteend: None,
+        milestone_resubmitted: false,
+        milestone_round: Uint128::zero(),
+        milestone_yes: Uint128::zero(),
+        milestone_no: Uint128::zero(),
+        milestone_abstain: Uint128::zero(),
+    }
+}
+
+fn mock_add_project() -> ExecuteMsg {
+    ExecuteMsg::AddProject{
+        creator_wallet: String::from("creator"),
+        project_description: String::from("demo"),
+        project_collected: Uint128::new(200),
+        project_email: String::from("demo@gmail.com"),
+        project_title: String::from("demo"),
+        project_website: String::from("https://demo"),
+        project_createddate: String::from("20211223"),
+        project_logo: String::from("logo"),
+        project_whitepaper: String::from("whitepaper"),
+        project_milestones: vec![mock_milestone(0, 100), mock_milestone(1, 100)],
+        project_company: "company".to_string(),
+        project_ecosystem: "terra".to_string(),
+        project_saft: "saft".to_string(),
+        project_teammembers: Vec::new(),
+        vesting: Vec::new(),
+        token_addr: "".to_string(),
+        milestone_quorum: None,
+        milestone_threshold: None,
+        milestone_voteperiod: None,
+        rejection_policy: None,
+    }
+}
+
+//ust sent to anchor by the DepositStable of a backing
+fn deposited(res: &Response) -> u128 {
+    let deposit = res.messages.iter().find(|sub| sub.id == DEPOSIT_REPLY_ID).unwrap();
+    match &deposit.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute{ funds, .. }) => funds[0].amount.u128(),
+        msg => panic!("unexpected deposit {:?}", msg),
+    }
+}
+
+//anchor answers the DepositStable, without mint_amount the contract uses the epoch rate
+fn mock_deposit_reply(deps: DepsMut, mint_amount: Option<u128>) {
+    let mut event = Event::new("wasm");
+    if let Some(amount) = mint_amount {
+        event = event.add_attribute("mint_amount", amount.to_string());
+    }
+    let msg = Reply{ id: DEPOSIT_REPLY_ID, result: ContractResult::Ok(SubMsgExecutionResponse{
+        events: vec![event], data: None
+    })};
+    reply(deps, mock_env(), msg).unwrap();
+}
+
+//the redeem brings received uusd into the contract, then the reply pays it out
+fn mock_redeem_reply(deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>, received: u128) -> Response {
+    let balance = deps.as_ref().querier.query_balance(MOCK_CONTRACT_ADDR, "uusd").unwrap().amount;
+    deps.querier.with_balance(MOCK_CONTRACT_ADDR, &[Coin::new(balance.u128() + received, "uusd")]);
+    let msg = Reply{ id: REDEEM_REPLY_ID, result: ContractResult::Ok(SubMsgExecutionResponse{
+        events: vec![], data: None
+    })};
+    reply(deps.as_mut(), mock_env(), msg).unwrap()
+}
+
+fn back_project(deps: DepsMut, wallet: &str, amount: u128) -> Response {
+    let msg = ExecuteMsg::Back2Project{
+        project_id: Uint128::new(1),
+        backer_wallet: String::from(wallet),
+        otherchain: "".to_string(),
+        otherchain_wallet: "".to_string(),
+        referrer: None,
+    };
+    let funds = Coin::new(amount * 105 / 100 * 1000000, "uusd");
+    execute(deps, mock_env(), mock_info(wallet, &[funds]), msg).unwrap()
+}
+
+//instantiate, add the project and approve it, collected is split between backers and community
+fn setup_fundraising_project(mut deps: DepsMut, add_project: ExecuteMsg) {
+    let info = mock_info("admin", &[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")),
+        wefund: Some(String::from("wefund")),
+        anchor_market: Some("market".to_string()),
+        aust_token: Some("aust".to_string()),
+        vesting_contract: None,
+        community_manager: Some(String::from("manager")),
+        timelock_period: None,
+        yield_split: None,
+        yield_strategy: None,
+        fee_schedule: None,
+    };
+    instantiate(deps.branch(), mock_env(), info.clone(), msg).unwrap();
+    execute(deps.branch(), mock_env(), info.clone(), add_project).unwrap();
+
+    let msg = ExecuteMsg::WefundApprove{ project_id: Uint128::new(1) };
+    execute(deps.branch(), mock_env(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::AddCommunitymember{ wallet: String::from("community1") };
+    execute(deps.branch(), mock_env(), info, msg).unwrap();
+}
+
+//instantiate, add the project and fund it until releasing
+//backers are (wallet, ust), community1 backs the community half
+fn setup_project(mut deps: DepsMut, mut add_project: ExecuteMsg, backers: &[(&str, u128)]) {
+    let total: u128 = backers.iter().map(|(_, amount)| amount).sum();
+    if let ExecuteMsg::AddProject{ ref mut project_collected, .. } = add_project {
+        *project_collected = Uint128::new(total * 2);
+    }
+    setup_fundraising_project(deps.branch(), add_project);
+
+    let mut backings = backers.to_vec();
+    backings.push(("community1", total));
+    for (wallet, amount) in backings {
+        let res = back_project(deps.branch(), wallet, amount);
+        mock_deposit_reply(deps.branch(), Some(deposited(&res)));
+    }
+}
+
+fn setup_releasing_project(deps: DepsMut) {
+    setup_project(deps, mock_add_project(), &[("backer1", 100)]);
+}
+
+fn load_project(deps: Deps, project_id: u128) -> ProjectState {
+    let res = query(deps, mock_env(), QueryMsg::GetProject{ project_id: Uint128::new(project_id) }).unwrap();
+    from_binary(&res).unwrap()
+}
+
+#[test]
+fn release_milestone_needs_approved_vote(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(200000000))],
+    )]);
+    deps.querier.with_exchange_rate(Decimal256::one());
+    setup_releasing_project(deps.as_mut());
+
+    let release = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+
+    //-----not voted yet-------------------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), release.clone()).unwrap_err();
+    match err {
+        ContractError::NotCorrectMilestoneStatus{ step, status } => {
+            assert_eq!(step, 0);
+            assert_eq!(status, MILESTONE_VOTING);
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer1"),
+        vote: VoteOption::Yes,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_APPROVED);
+
+    //-----only creator or owner can release------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), release.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), release.clone()).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, REDEEM_REPLY_ID);
+
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_RELEASED);
+    assert_eq!(project.project_milestonestep, Uint128::new(1));
+    assert_eq!(project.project_status, ProjectStatus::Releasing);
+
+    //-----next milestone needs its own vote---------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), release).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectMilestoneStatus{ step: 1, .. }));
+}
+
+#[test]
+fn milestone_vote_needs_backer_or_delegate(){
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+
+    let vote = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer1"),
+        vote: VoteOption::Yes,
+    };
+
+    //-----nobody can vote in the name of a backer-------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), vote.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NotBackerWallet{}));
+
+    //-----non voter wallet---------------------------------
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("anyone"),
+        vote: VoteOption::Yes,
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotBackerWallet{}));
+
+    //-----only backers can register a delegate----------------
+    let msg = ExecuteMsg::SetVoteDelegate{
+        project_id: Uint128::new(1),
+        delegate: Some(String::from("delegate1")),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NotBackerWallet{}));
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+
+    let env = mock_env();
+    execute(deps.as_mut(), env.clone(), mock_info("delegate1", &[]), vote).unwrap();
+
+    let project = load_project(deps.as_ref(), 1);
+    let vote = project.milestone_states[0].milestone_votes.iter()
+        .find(|vote| vote.wallet == "backer1").unwrap();
+    assert_eq!(vote.vote, Some(VoteOption::Yes));
+    assert_eq!(vote.submitted_by, Some(Addr::unchecked("delegate1")));
+    assert_eq!(vote.submitted_at, Some(Uint128::from(env.block.time.seconds())));
+}
+
+#[test]
+fn weighted_milestone_vote(){
+    //-----backer2 alone holds 75% of the weight-----------------
+    let mut deps = mock_dependencies(&[]);
+    setup_project(deps.as_mut(), mock_add_project(), &[("backer1", 100), ("backer2", 300)]);
+
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer2"),
+        vote: VoteOption::Yes,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("backer2", &[]), msg.clone()).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_APPROVED);
+    assert_eq!(project.milestone_states[0].milestone_yes, Uint128::new(300000000));
+    let votes = &project.milestone_states[0].milestone_votes;
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].weight, Uint128::new(300000000));
+
+    //-----once voted, can't vote again--------------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer2", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectMilestoneStatus{ .. }));
+
+    //-----abstain counts for quorum but not for approval------------
+    let mut deps = mock_dependencies(&[]);
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut milestone_quorum, ref mut milestone_threshold, .. } = add_project {
+        *milestone_quorum = Some(Uint128::new(80));
+        *milestone_threshold = Some(Uint128::new(60));
+    }
+    setup_project(deps.as_mut(), add_project, &[("backer1", 100), ("backer2", 300)]);
+
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer1"),
+        vote: VoteOption::Yes,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg.clone()).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_VOTING);
+
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyVoted{}));
+
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer2"),
+        vote: VoteOption::Abstain,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("backer2", &[]), msg).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_APPROVED);
+
+    //-----a no from the majority rejects right away--------------
+    let mut deps = mock_dependencies(&[]);
+    setup_project(deps.as_mut(), mock_add_project(), &[("backer1", 100), ("backer2", 300)]);
+
+    let msg = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer2"),
+        vote: VoteOption::No,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("backer2", &[]), msg).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_REJECTED);
+
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectMilestoneStatus{ .. }));
+
+    //-----tallies above the stored total, as a migrated project may have, don't underflow------
+    let mut milestone = mock_milestone(0, 100);
+    milestone.milestone_yes = Uint128::new(60);
+    milestone.milestone_abstain = Uint128::new(300);
+    let status = calc_milestone_status(&milestone, Uint128::new(200), Uint128::new(50), Uint128::new(50), false);
+    assert_eq!(status, MILESTONE_APPROVED);
+
+    //-----invalid rule------------------------
+    let mut deps = mock_dependencies(&[]);
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut milestone_threshold, .. } = add_project {
+        *milestone_threshold = Some(Uint128::new(101));
+    }
+    let msg = InstantiateMsg{
+        admin: None, wefund: None, anchor_market: None, aust_token: None, vesting_contract: None,
+        community_manager: None, timelock_period: None, yield_split: None, yield_strategy: None, fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), add_project).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidVoteRule{ .. }));
+}
+
+#[test]
+fn milestone_voting_window(){
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+
+    let project = load_project(deps.as_ref(), 1);
+    let voteend = project.milestone_states[0].milestone_voteend.unwrap();
+    assert_eq!(project.milestone_states[0].milestone_votestart,
+        Some(Uint128::from(mock_env().block.time.seconds())));
+    assert!(project.milestone_states[1].milestone_votestart.is_none());
+
+    let mut late = mock_env();
+    late.block.time = late.block.time.plus_seconds(voteend.u128() as u64 - late.block.time.seconds() + 1);
+
+    //-----vote after the window is refused-----------------
+    let vote = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer1"),
+        vote: VoteOption::Yes,
+    };
+    let err = execute(deps.as_mut(), late.clone(), mock_info("backer1", &[]), vote.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::VotingWindowClosed{}));
+
+    //-----closing only after the window----------------------
+    let close = ExecuteMsg::CloseMilestoneVote{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), close.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::VotingWindowOpen{}));
+
+    execute(deps.as_mut(), late.clone(), mock_info("anyone", &[]), close).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_REJECTED);
+    assert_eq!(project.project_status, ProjectStatus::Releasing);
+
+    //-----one resubmission by the creator-----------------
+    let resubmit = ExecuteMsg::ResubmitMilestone{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), late.clone(), mock_info("anyone", &[]), resubmit.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    execute(deps.as_mut(), late.clone(), mock_info("creator", &[]), resubmit.clone()).unwrap();
+
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_VOTING);
+    assert!(project.milestone_states[0].milestone_resubmitted);
+    assert_eq!(project.milestone_states[0].milestone_votestart,
+        Some(Uint128::from(late.block.time.seconds())));
+
+    execute(deps.as_mut(), late.clone(), mock_info("backer1", &[]), vote).unwrap();
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_APPROVED);
+
+    //-----fail policy goes straight to the fail flow-----------------
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(200000000))],
+    )]);
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut rejection_policy, .. } = add_project {
+        *rejection_policy = Some(RejectionPolicy::Fail);
+    }
+    setup_project(deps.as_mut(), add_project, &[("backer1", 100)]);
+
+    let vote = ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1),
+        wallet: String::from("backer1"),
+        vote: VoteOption::No,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.value == "project failed"));
+    let project = load_project(deps.as_ref(), 1);
+    assert_eq!(project.milestone_states[0].milestone_status, MILESTONE_REJECTED);
+    assert_eq!(project.project_status, ProjectStatus::Fail);
+
+    let resubmit = ExecuteMsg::ResubmitMilestone{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), resubmit).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectStatus{ .. }));
+
+    //-----a resubmission starts a new round, the old votes stop counting-----------
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+    let vote = |vote: VoteOption| ExecuteMsg::SetMilestoneVote{
+        project_id: Uint128::new(1), wallet: String::from("backer1"), vote };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote(VoteOption::No)).unwrap();
+    let resubmit = ExecuteMsg::ResubmitMilestone{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), resubmit).unwrap();
+    let milestone = &load_project(deps.as_ref(), 1).milestone_states[0];
+    assert_eq!(milestone.milestone_round, Uint128::new(1));
+    assert_eq!(milestone.milestone_no, Uint128::zero());
+    assert!(milestone.milestone_votes.is_empty());
+
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote(VoteOption::Yes)).unwrap();
+    let milestone = &load_project(deps.as_ref(), 1).milestone_states[0];
+    assert_eq!(milestone.milestone_status, MILESTONE_APPROVED);
+    assert_eq!(milestone.milestone_votes[0].round, Uint128::new(1));
+}
+
+#[test]
+fn claim_refund_after_failure(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(400000000))],
+    )]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_project(deps.as_mut(), mock_add_project(), &[("backer1", 100), ("backer2", 100)]);
+
+    //-----claim only after failure------------------
+    let claim = ExecuteMsg::ClaimRefund{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectStatus{ .. }));
+
+    let msg = ExecuteMsg::FailProject{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 1); //only redeem, no push refund
+    let pool: u128 = res.attributes.iter().find(|attr| attr.key == "refund_amount")
+        .unwrap().value.parse().unwrap();
+    mock_redeem_reply(&mut deps, pool);
+
+    let msg = QueryMsg::GetClaimableRefund{ project_id: Uint128::new(1), wallet: String::from("community1") };
+    let claimable: Uint128 = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(claimable, Uint128::new(pool / 2));
+
+    for &(wallet, amount) in &[("backer1", pool / 4), ("backer2", pool / 4), ("community1", pool / 2)] {
+        let res = execute(deps.as_mut(), mock_env(), mock_info(wallet, &[]), claim.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{
+            to_address: wallet.to_string(),
+            amount: vec![Coin::new(amount, "uusd")],
+        }));
+    }
+
+    //-----nothing left------------------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+
+    //-----a project still fundraising can fail too, its backers get everything back-----
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+    let res = back_project(deps.as_mut(), "backer1", 50);
+    mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
+    let msg = ExecuteMsg::FailProject{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let pool: u128 = res.attributes.iter().find(|attr| attr.key == "refund_amount")
+        .unwrap().value.parse().unwrap();
+    mock_redeem_reply(&mut deps, pool);
+    let res = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), claim).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{
+        to_address: "backer1".to_string(),
+        amount: vec![Coin::new(pool, "uusd")],
+    }));
+}
+
+#[test]
+fn access_control(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(200000000))],
+    )]);
+    setup_releasing_project(deps.as_mut());
+
+    let project_id = Uint128::new(1);
+    let msgs = vec![
+        ExecuteMsg::CompleteProject{ project_id },
+        ExecuteMsg::FailProject{ project_id },
+        ExecuteMsg::SetFundraisingStage{ project_id, stage: Uint128::new(1) },
+        ExecuteMsg::AddCommunitymember{ wallet: String::from("community2") },
+        ExecuteMsg::RemoveCommunitymember{ wallet: String::from("community1") },
+        ExecuteMsg::WefundApprove{ project_id },
+        ExecuteMsg::SetProjectStatus{ project_id, status: Uint128::new(3) },
+    ];
+    for msg in msgs {
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized{}));
+    }
+
+    //-----community manager handles community, nothing else----------
+    let msg = ExecuteMsg::AddCommunitymember{ wallet: String::from("community2") };
+    execute(deps.as_mut(), mock_env(), mock_info("manager", &[]), msg).unwrap();
+    let msg = ExecuteMsg::FailProject{ project_id };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("manager", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+
+    //-----creator can't set its fundraising stage, nor complete-----------
+    let msg = ExecuteMsg::SetFundraisingStage{ project_id, stage: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let msg = ExecuteMsg::CompleteProject{ project_id };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+
+    //-----operator completes once the current milestone passed its vote, both milestones go-----
+    let msg = ExecuteMsg::CompleteProject{ project_id };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("wefund", &[]), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectMilestoneStatus{ step: 0, .. }));
+    let vote = ExecuteMsg::SetMilestoneVote{ project_id, wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote).unwrap();
+    assert_eq!(load_project(deps.as_ref(), 1).milestone_states[1].milestone_status, MILESTONE_VOTING);
+    execute(deps.as_mut(), mock_env(), mock_info("wefund", &[]), msg.clone()).unwrap();
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.milestone_states[0].milestone_status, MILESTONE_RELEASED);
+    assert_eq!(x.milestone_states[1].milestone_status, MILESTONE_RELEASED);
+    assert_eq!(x.project_status, ProjectStatus::Done);
+    assert_eq!(x.released_amount, x.backerbacked_amount + x.communitybacked_amount);
+    let err = execute(deps.as_mut(), mock_env(), mock_info("wefund", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectStatus{ .. }));
+
+    //-----an invalid address is an error, never the default---------
+    for field in 0..6 {
+        let mut deps = mock_dependencies(&[]);
+        let addr = |i: usize| if i == field { Some(String::from("x")) } else { None };
+        let msg = InstantiateMsg{
+            admin: addr(0), wefund: addr(1), anchor_market: addr(2), aust_token: addr(3),
+            vesting_contract: addr(4), community_manager: addr(5), timelock_period: None,
+            yield_split: None, yield_strategy: None, fee_schedule: None,
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress{}));
+    }
+}
+
+#[test]
+fn role_registry(){
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: Some(String::from("wefund")),
+        anchor_market: None, aust_token: None, vesting_contract: None, community_manager: None,
+        timelock_period: None,
+        yield_split: None,
+        yield_strategy: None,
+        fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), mock_add_project()).unwrap();
+
+    let roles = |deps: Deps, address: &str| -> Vec<Role> {
+        let msg = QueryMsg::GetRoles{ address: address.to_string() };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    assert_eq!(roles(deps.as_ref(), "admin"), vec![Role::Owner, Role::CommunityManager]);
+    assert_eq!(roles(deps.as_ref(), "wefund"), vec![Role::Operator]);
+
+    //-----only owner grants, owner and creator are not grantable-----------
+    let grant = ExecuteMsg::GrantRole{ address: String::from("approver"), role: Role::ProjectApprover };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("wefund", &[]), grant.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let msg = ExecuteMsg::GrantRole{ address: String::from("approver"), role: Role::Owner };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotGrantableRole{}));
+
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), grant).unwrap();
+    let msg = ExecuteMsg::GrantRole{ address: String::from("approver"), role: Role::Treasurer };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(roles(deps.as_ref(), "approver"), vec![Role::ProjectApprover, Role::Treasurer]);
+
+    //-----granted approver can approve, but not change config-------------
+    let msg = ExecuteMsg::SetConfig{ wefund: None, community_manager: None, yield_split: None,
+        referral_share: None };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("approver", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let approve = ExecuteMsg::WefundApprove{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("approver", &[]), approve.clone()).unwrap();
+
+    //-----revoke and renounce---------------------------
+    let msg = ExecuteMsg::RevokeRole{ address: String::from("approver"), role: Role::ProjectApprover };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg.clone()).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotGrantedRole{}));
+
+    let msg = ExecuteMsg::RenounceRole{ role: Role::Treasurer };
+    execute(deps.as_mut(), mock_env(), mock_info("approver", &[]), msg).unwrap();
+    assert_eq!(roles(deps.as_ref(), "approver"), vec![]);
 }
 
+#[test]
+fn ownership_handover(){
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: None,
+        anchor_market: None, aust_token: None, vesting_contract: None, community_manager: None,
+        timelock_period: None,
+        yield_split: None,
+        yield_strategy: None,
+        fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let config = |deps: Deps| -> Config {
+        from_binary(&query(deps, mock_env(), QueryMsg::GetConfig{}).unwrap()).unwrap()
+    };
+
+    //-----invalid address is an error, not ignored-----------------
+    let msg = ExecuteMsg::SetConfig{ wefund: Some(String::from("x")), community_manager: None, yield_split: None,
+        referral_share: None };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidAddress{}));
+
+    let propose = ExecuteMsg::ProposeNewOwner{ new_owner: String::from("newowner"), expires_in: Uint128::new(100) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("newowner", &[]), propose.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose.clone()).unwrap();
+
+    let pending = config(deps.as_ref()).pending_owner.unwrap();
+    assert_eq!(pending.owner, Addr::unchecked("newowner"));
+    assert_eq!(config(deps.as_ref()).owner, Addr::unchecked("admin"));
+
+    //-----an expiry past the end of time is an error, not a panic-------
+    let msg = ExecuteMsg::ProposeNewOwner{ new_owner: String::from("newowner"), expires_in: Uint128::MAX };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Std(StdError::Overflow{ .. })));
+
+    //-----cancel, then expired, then accept----------------------
+    let msg = ExecuteMsg::CancelOwnershipProposal{};
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("newowner", &[]), ExecuteMsg::AcceptOwnership{}).unwrap_err();
+    assert!(matches!(err, ContractError::NoOwnershipProposal{}));
+
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose).unwrap();
+    let mut late = mock_env();
+    late.block.time = late.block.time.plus_seconds(101);
+    let err = execute(deps.as_mut(), late, mock_info("newowner", &[]), ExecuteMsg::AcceptOwnership{}).unwrap_err();
+    assert!(matches!(err, ContractError::OwnershipProposalExpired{}));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::AcceptOwnership{}).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+
+    execute(deps.as_mut(), mock_env(), mock_info("newowner", &[]), ExecuteMsg::AcceptOwnership{}).unwrap();
+    let config = config(deps.as_ref());
+    assert_eq!(config.owner, Addr::unchecked("newowner"));
+    assert_eq!(config.pending_owner, None);
+}
+
+#[test]
+fn timelocked_config_change(){
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: None,
+        anchor_market: Some(String::from("market")), aust_token: None, vesting_contract: None,
+        community_manager: None, timelock_period: Some(Uint128::new(1000)), yield_split: None, yield_strategy: None, fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let config = |deps: Deps| -> Config {
+        from_binary(&query(deps, mock_env(), QueryMsg::GetConfig{}).unwrap()).unwrap()
+    };
+    let pending = |deps: Deps| -> Vec<ConfigChange> {
+        from_binary(&query(deps, mock_env(), QueryMsg::GetPendingConfigChanges{}).unwrap()).unwrap()
+    };
+
+    let propose = ExecuteMsg::ProposeConfigChange{ anchor_market: Some(String::from("newmarket")),
+        aust_token: None, vesting_contract: None, timelock_period: None, yield_strategy: None };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), propose.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose.clone()).unwrap();
+
+    let changes = pending(deps.as_ref());
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].anchor_market, Some(Addr::unchecked("newmarket")));
+    assert_eq!(changes[0].executable_at, changes[0].proposed_at + Uint128::new(1000));
+
+    //-----not before the delay-----------------------
+    let msg = ExecuteMsg::ExecuteConfigChange{ id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::ConfigChangeTimelocked{ .. }));
+    assert_eq!(config(deps.as_ref()).anchor_market, Addr::unchecked("market"));
+
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(1000);
+    execute(deps.as_mut(), later.clone(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(config(deps.as_ref()).anchor_market, Addr::unchecked("newmarket"));
+    assert!(pending(deps.as_ref()).is_empty());
+
+    //-----owner cancels a queued change---------------
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose).unwrap();
+    let msg = ExecuteMsg::CancelConfigChange{ id: Uint128::new(2) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(pending(deps.as_ref()).is_empty());
+    let msg = ExecuteMsg::ExecuteConfigChange{ id: Uint128::new(2) };
+    execute(deps.as_mut(), later, mock_info("admin", &[]), msg).unwrap_err();
+}
+
+#[test]
+fn pause_operations(){
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+
+    let msg = ExecuteMsg::GrantRole{ address: String::from("guardian"), role: Role::Guardian };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    //-----guardian pauses voting and creation-----------------
+    let pause = ExecuteMsg::Pause{ operations: vec![PauseOperation::Voting, PauseOperation::ProjectCreation] };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), pause.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), pause).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPauseState{}).unwrap();
+    let pause: PauseState = from_binary(&res).unwrap();
+    assert_eq!(pause, PauseState{ backing: false, voting: true, releases: false, project_creation: true });
+
+    let vote = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::Voting }));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), mock_add_project()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::ProjectCreation }));
+
+    //-----the window can't be closed on a frozen tally, nor the milestone resubmitted------
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(365 * 86400);
+    let msg = ExecuteMsg::CloseMilestoneVote{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), later, mock_info("anyone", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::Voting }));
+    let msg = ExecuteMsg::ResubmitMilestone{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::Voting }));
+
+    //-----only the owner unpauses--------------------------
+    let unpause = ExecuteMsg::Unpause{ operations: vec![PauseOperation::Voting] };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), unpause.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), unpause).unwrap();
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote).unwrap();
+
+    //-----releases freeze release and complete---------------
+    let pause = ExecuteMsg::Pause{ operations: vec![PauseOperation::Releases] };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), pause).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::Releases }));
+    let msg = ExecuteMsg::CompleteProject{ project_id: Uint128::new(1) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused{ operation: PauseOperation::Releases }));
+}
+
+#[test]
+fn paginated_project_listing(){
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+
+    //-----two more projects, one by another creator on another chain------
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), mock_add_project()).unwrap();
+    let mut msg = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut creator_wallet, ref mut project_ecosystem, .. } = msg {
+        *creator_wallet = String::from("creator2");
+        *project_ecosystem = String::from("juno");
+    }
+    execute(deps.as_mut(), mock_env(), mock_info("creator2", &[]), msg).unwrap();
+
+    let list = |deps: Deps, msg: QueryMsg| -> AllProjectResponse {
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+
+    let res = list(deps.as_ref(), QueryMsg::GetAllProject{ start_after: None, limit: Some(2),
+        status: None, creator: None, ecosystem: None, under_cap: None });
+    assert_eq!(res.projects.len(), 2);
+    assert_eq!(res.next_key, Some(Uint128::new(2)));
+    let res = list(deps.as_ref(), QueryMsg::GetAllProject{ start_after: res.next_key, limit: Some(2),
+        status: None, creator: None, ecosystem: None, under_cap: None });
+    assert_eq!(res.projects.len(), 1);
+    assert_eq!(res.projects[0].project_id, Uint128::new(3));
+    assert_eq!(res.next_key, None);
+
+    //-----filters-------------------------------
+    let res = list(deps.as_ref(), QueryMsg::GetAllProject{ start_after: None, limit: None,
+        status: Some(ProjectStatus::Releasing), creator: None, ecosystem: None, under_cap: None });
+    assert_eq!(res.projects.len(), 1);
+    assert_eq!(res.projects[0].project_id, Uint128::new(1));
+
+    let res = list(deps.as_ref(), QueryMsg::GetAllProject{ start_after: None, limit: None,
+        status: None, creator: Some(String::from("creator2")), ecosystem: None, under_cap: None });
+    assert_eq!(res.projects.len(), 1);
+    assert_eq!(res.projects[0].project_ecosystem, "juno");
+
+    let res = list(deps.as_ref(), QueryMsg::GetAllProject{ start_after: None, limit: None,
+        status: None, creator: None, ecosystem: Some(String::from("terra")), under_cap: Some(true) });
+    assert_eq!(res.projects.iter().map(|x| x.project_id.u128()).collect::<Vec<_>>(), vec![2]);
+
+    //-----a full backer half is over the cap, whatever the community half holds------
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+    let res = back_project(deps.as_mut(), "backer1", 100);
+    mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
+    let under_cap = |deps: Deps, under_cap: bool| -> Vec<u128> {
+        list(deps, QueryMsg::GetAllProject{ start_after: None, limit: None, status: None, creator: None,
+            ecosystem: None, under_cap: Some(under_cap) }).projects.iter().map(|x| x.project_id.u128()).collect()
+    };
+    assert!(under_cap(deps.as_ref(), true).is_empty());
+    assert_eq!(under_cap(deps.as_ref(), false), vec![1]);
+    let err = execute(deps.as_mut(), mock_env(), mock_info("backer2", &[Coin::new(10000000, "uusd")]),
+        ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer2"),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(), referrer: None }).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyCollected{}));
+}
+
+#[test]
+fn project_indexes_follow_updates(){
+    let mut deps = mock_dependencies(&[]);
+    setup_releasing_project(deps.as_mut());
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), mock_add_project()).unwrap();
+
+    let by_status = |deps: Deps, status: ProjectStatus| -> Vec<u128> {
+        projectstates().idx.status.prefix(status_key(&status))
+            .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|item| item.unwrap().1.project_id.u128())
+            .collect()
+    };
+    assert_eq!(by_status(deps.as_ref(), ProjectStatus::Releasing), vec![1]);
+    assert_eq!(by_status(deps.as_ref(), ProjectStatus::WefundVote), vec![2]);
+
+    let by_creator = projectstates().idx.creator.prefix(b"creator".to_vec())
+        .range(&deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .count();
+    assert_eq!(by_creator, 2);
+
+    //-----status change moves the project between index entries------
+    let msg = ExecuteMsg::WefundApprove{ project_id: Uint128::new(2) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(by_status(deps.as_ref(), ProjectStatus::WefundVote).is_empty());
+    assert_eq!(by_status(deps.as_ref(), ProjectStatus::Fundraising), vec![2]);
+
+    //-----only fundraising and releasing projects count as deposited-----
+    let x = load_project(deps.as_ref(), 1);
+    let ledger: Ledger = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetLedger{}).unwrap()).unwrap();
+    assert_eq!(ledger.outstanding().unwrap(), x.backerbacked_amount + x.communitybacked_amount);
+
+    let msg = ExecuteMsg::RemoveProject{ project_id: Uint128::new(2) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(by_status(deps.as_ref(), ProjectStatus::Fundraising).is_empty());
+}
+
+#[test]
+fn normalized_project_storage(){
+    let mut deps = mock_dependencies(&[]);
+    let mut msg = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut project_teammembers, .. } = msg {
+        project_teammembers.push(TeamMember{
+            teammember_description: String::from("dev"),
+            teammember_linkedin: String::from(""),
+            teammember_role: String::from("cto"),
+        });
+    }
+    setup_project(deps.as_mut(), msg, &[("backer1", 100), ("backer1", 100), ("backer2", 200)]);
+
+    //-----stored project keeps no embedded vectors-------------
+    let stored = projectstates().load(&deps.storage, 1u128.into()).unwrap();
+    assert!(stored.backer_states.is_empty());
+    assert!(stored.milestone_states.is_empty());
+    assert!(stored.teammember_states.is_empty());
+
+    //-----queries put the same shape back together----------
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetBacker{ project_id: Uint128::new(1) }).unwrap();
+    let backers: Vec<BackerState> = from_binary(&res).unwrap();
+    assert_eq!(backers.len(), 2);
+    assert_eq!(backers[0].backer_wallet, Addr::unchecked("backer1"));
+    assert_eq!(backers[0].ust_amount.amount, Uint128::new(200 * 1000000));
+
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.communitybacker_states.len(), 1);
+    assert_eq!(x.teammember_states[0].teammember_role, "cto");
+    assert_eq!(x.milestone_states.len(), 2);
+    assert!(x.milestone_states[0].milestone_votestart.is_some());
+
+    //-----a vote is stored when it is cast, weighted by the wallet's backings-----
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Abstain };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let x = load_project(deps.as_ref(), 1);
+    let weights: Vec<u128> = x.milestone_states[0].milestone_votes.iter().map(|v| v.weight.u128()).collect();
+    assert_eq!(weights, vec![200 * 1000000]);
+    assert_eq!(x.milestone_states[0].milestone_abstain, Uint128::new(200 * 1000000));
+    assert!(x.milestone_states[1].milestone_votes.is_empty());
+
+    //-----removing the project clears every part--------------
+    let msg = ExecuteMsg::RemoveProject{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(load_backers(&deps.storage, Uint128::new(1), false).unwrap().is_empty());
+    assert!(load_milestones(&deps.storage, Uint128::new(1)).unwrap().is_empty());
+    assert!(load_votes(&deps.storage, Uint128::new(1), Uint128::zero()).unwrap().is_empty());
+}
+
+fn mock_legacy_project(id: u128, status: ProjectStatus) -> LegacyProjectState {
+    let backer = |wallet: &str, amount: u128| BackerState{
+        backer_wallet: Addr::unchecked(wallet),
+        ust_amount: Coin::new(amount, "uusd"),
+        aust_amount: Coin::new(0, "aust"),
+        otherchain: String::from(""),
+        otherchain_wallet: String::from(""),
+    };
+    let milestone = |step: u128| LegacyMilestone{
+        milestone_step: Uint128::new(step),
+        milestone_name: format!("milestone{}", step + 1),
+        milestone_description: String::from(""),
+        milestone_startdate: String::from(""),
+        milestone_enddate: String::from(""),
+        milestone_amount: Uint128::new(100),
+        milestone_status: Uint128::zero(),
+        milestone_votes: vec![
+            LegacyVote{ wallet: Addr::unchecked("wefund"), voted: false },
+            LegacyVote{ wallet: Addr::unchecked("backer1"), voted: step == 0 },
+        ],
+    };
+    LegacyProjectState{
+        project_company: String::from("company"),
+        project_title: String::from("title"),
+        project_description: String::from(""),
+        project_ecosystem: String::from("terra"),
+        project_createddate: String::from(""),
+        project_saft: String::from(""),
+        project_logo: String::from(""),
+        project_whitepaper: String::from(""),
+        project_website: String::from(""),
+        project_email: String::from(""),
+        project_id: Uint128::new(id),
+        creator_wallet: Addr::unchecked("creator"),
+        project_collected: Uint128::new(400),
+        project_status: status,
+        fundraising_stage: Uint128::zero(),
+        backerbacked_amount: Uint128::new(200),
+        communitybacked_amount: Uint128::new(200),
+        backer_states: vec![backer("backer1", 50), backer("backer1", 50), backer("backer2", 100)],
+        communitybacker_states: vec![backer("community1", 200)],
+        milestone_states: vec![milestone(0), milestone(1)],
+        project_milestonestep: Uint128::zero(),
+        teammember_states: Vec::new(),
+        vesting: Vec::new(),
+        token_addr: Addr::unchecked(""),
+    }
+}
+
+#[test]
+fn migrate_legacy_state(){
+    let mut deps = mock_dependencies(&[Coin::new(8000000, "uusd")]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(1000))],
+    )]);
+
+    //-----state as written by the previous release--------------
+    set_contract_version(&mut deps.storage, "WEFUND", "17.2.0").unwrap();
+    let legacy_config: Item<LegacyConfig> = Item::new("config");
+    legacy_config.save(&mut deps.storage, &LegacyConfig{
+        owner: Addr::unchecked("admin"),
+        wefund: Addr::unchecked("wefund"),
+        anchor_market: Addr::unchecked("market"),
+        aust_token: Addr::unchecked("aust"),
+        vesting_contract: Addr::unchecked(""),
+    }).unwrap();
+    let legacy_projects: Map<U128Key, LegacyProjectState> = Map::new("prj");
+    legacy_projects.save(&mut deps.storage, 1u128.into(), &mock_legacy_project(1, ProjectStatus::Releasing)).unwrap();
+    legacy_projects.save(&mut deps.storage, 2u128.into(), &mock_legacy_project(2, ProjectStatus::Fundraising)).unwrap();
+    PROJECT_SEQ.save(&mut deps.storage, &Uint128::new(2)).unwrap();
+
+    //-----first page in migrate, the rest with MigrateProjects------
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg{ limit: Some(1) }).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "migration_done" && attr.value == "false"));
+    let config: Config = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetConfig{}).unwrap()).unwrap();
+    assert_eq!(config.community_manager, Addr::unchecked("admin"));
+
+    //-----nothing else runs while old projects are left---------------
+    let msg = ExecuteMsg::Pause{ operations: vec![PauseOperation::Voting] };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::MigrationInProgress{}));
+
+    let msg = ExecuteMsg::MigrateProjects{ limit: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "migration_done" && attr.value == "true"));
+
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.backer_states.len(), 2);
+    assert_eq!(x.backer_states[0].ust_amount.amount, Uint128::new(100));
+    assert_eq!(x.milestone_quorum, Uint128::new(50));
+    let votes = &x.milestone_states[0].milestone_votes;
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].wallet, Addr::unchecked("backer1"));
+    assert_eq!(votes[0].vote, Some(VoteOption::Yes));
+    assert_eq!(x.milestone_states[0].milestone_yes, votes[0].weight);
+    assert!(x.milestone_states[0].milestone_voteend.is_some());
+    assert!(load_project(deps.as_ref(), 2).milestone_states[0].milestone_votes.is_empty());
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAllProject{ start_after: None, limit: None,
+        status: Some(ProjectStatus::Fundraising), creator: None, ecosystem: None, under_cap: None }).unwrap();
+    let res: AllProjectResponse = from_binary(&res).unwrap();
+    assert_eq!(res.projects.len(), 1);
+
+    //-----fees and yield the old release kept are left to TransferAllCoins, not the projects' 800 aust-----
+    let x2 = load_project(deps.as_ref(), 2);
+    assert_eq!(x.aust_amount + x2.aust_amount, Uint128::new(800));
+    let msg = ExecuteMsg::TransferAllCoins{ wallet: String::from("treasury") };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{ to_address: String::from("treasury"),
+        amount: vec![Coin::new(8000000, "uusd")] }));
+    assert_eq!(res.messages[1].msg, CosmosMsg::Wasm(WasmMsg::Execute{ contract_addr: String::from("aust"),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer{ recipient: String::from("treasury"), amount: Uint128::new(200) })
+            .unwrap(), funds: Vec::new() }));
+
+    //-----migrating again is a no-op, never downgrade------------
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg{ limit: None }).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "migration_done" && attr.value == "true"));
+    assert_eq!(load_project(deps.as_ref(), 1), x);
+    let msg = ExecuteMsg::Pause{ operations: vec![PauseOperation::Voting] };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    set_contract_version(&mut deps.storage, "WEFUND", "99.0.0").unwrap();
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg{ limit: None }).unwrap_err();
+    assert!(matches!(err, ContractError::CannotDowngrade{ .. }));
+    set_contract_version(&mut deps.storage, "OTHER", "1.0.0").unwrap();
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg{ limit: None }).unwrap_err();
+    assert!(matches!(err, ContractError::CannotMigrate{ .. }));
+}
+
+#[test]
+fn deposit_ledger(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[(
+        &"aust".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(200000000))],
+    )]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_releasing_project(deps.as_mut());
+
+    let ledger = |deps: Deps| -> Ledger {
+        from_binary(&query(deps, mock_env(), QueryMsg::GetLedger{}).unwrap()).unwrap()
+    };
+    let x = load_project(deps.as_ref(), 1);
+    let backed = x.backerbacked_amount + x.communitybacked_amount;
+    assert_eq!(ledger(deps.as_ref()), Ledger{
+        total_deposited: backed, total_released: Uint128::zero(), total_refunded: Uint128::zero()
+    });
+
+    //-----release moves the principal actually paid out--------------
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    let released = Uint128::new(99999999); //100 ust in shares, rounded down twice
+    assert_eq!(ledger(deps.as_ref()).total_released, released);
+    assert_eq!(load_project(deps.as_ref(), 1).released_amount, released);
+    assert_eq!(ledger(deps.as_ref()).outstanding().unwrap(), backed - released);
+
+    //-----failing refunds the rest-------------------------
+    let msg = ExecuteMsg::FailProject{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let ledger = ledger(deps.as_ref());
+    assert_eq!(ledger.total_refunded, backed - released);
+    assert_eq!(ledger.outstanding().unwrap(), Uint128::zero());
+
+    //-----milestones can't add up to more than is collected---------------
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut project_milestones, .. } = add_project {
+        project_milestones[0].milestone_amount = Uint128::new(150);
+    }
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), add_project).unwrap_err();
+    assert!(matches!(err, ContractError::MilestonesOverCollected{ .. }));
+}
+
+#[test]
+fn aust_share_accounting(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+
+    //-----anchor reports the mint, or the epoch rate is used------------
+    let res = back_project(deps.as_mut(), "backer1", 100);
+    assert_eq!(deposited(&res), 100000000);
+    mock_deposit_reply(deps.as_mut(), Some(95000000));
+    back_project(deps.as_mut(), "community1", 100);
+    mock_deposit_reply(deps.as_mut(), None);
+
+    let backers = load_backers(&deps.storage, Uint128::new(1), false).unwrap();
+    assert_eq!(backers[0].aust_amount.amount, Uint128::new(95000000));
+    let community = load_backers(&deps.storage, Uint128::new(1), true).unwrap();
+    assert_eq!(community[0].aust_amount.amount, Uint128::new(99999900)); //100ust / 1.000001
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.project_status, ProjectStatus::Releasing);
+    assert_eq!(x.aust_amount, Uint128::new(194999900));
+
+    let msg = Reply{ id: 99, result: ContractResult::Ok(SubMsgExecutionResponse{ events: vec![], data: None }) };
+    let err = reply(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownReply{ id: 99 }));
+
+    //-----release redeems from the project's own aust---------------
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    assert_eq!(load_project(deps.as_ref(), 1).aust_amount, Uint128::new(95000000));
+
+    //-----refunds split by aust share, not by ust backed--------------
+    let msg = ExecuteMsg::FailProject{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let pool = Uint128::new(95000095); //95 aust * 1.000001
+    assert!(res.attributes.iter().any(|attr| attr.key == "refund_amount" && attr.value == pool.to_string()));
+    mock_redeem_reply(&mut deps, pool.u128());
+    assert_eq!(load_project(deps.as_ref(), 1).aust_amount, Uint128::zero());
+
+    let claimable = |deps: Deps, wallet: &str| -> Uint128 {
+        let msg = QueryMsg::GetClaimableRefund{ project_id: Uint128::new(1), wallet: String::from(wallet) };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    assert_eq!(claimable(deps.as_ref(), "backer1"), pool.multiply_ratio(95000000u128, 194999900u128));
+    assert_eq!(claimable(deps.as_ref(), "community1"), pool.multiply_ratio(99999900u128, 194999900u128));
+}
+
+#[test]
+fn yield_distribution(){
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_releasing_project(deps.as_mut()); //200 aust minted 1:1
+
+    let msg = ExecuteMsg::SetConfig{ wefund: None, community_manager: None, yield_split: Some(YieldSplit{
+        backers: Uint128::new(50), creator: Uint128::new(30), wefund: Uint128::new(30) }),
+        referral_share: None };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidYieldSplit{ .. }));
+
+    //-----rate goes up, 200 aust now covers the principal with 18181983 aust to spare------
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.100001").unwrap());
+    let accrued = |deps: Deps, wallet: &str| -> Uint128 {
+        let msg = QueryMsg::GetAccruedYield{ project_id: Uint128::new(1), wallet: String::from(wallet) };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    assert_eq!(accrued(deps.as_ref(), "creator"), Uint128::new(6000058)); //30%
+    assert_eq!(accrued(deps.as_ref(), "wefund"), Uint128::new(4000039)); //20%
+    assert_eq!(accrued(deps.as_ref(), "backer1"), Uint128::new(5000049)); //half of 50%
+    assert_eq!(accrued(deps.as_ref(), "anyone"), Uint128::zero());
+
+    let claim = ExecuteMsg::ClaimYield{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), claim.clone()).unwrap();
+    let res = mock_redeem_reply(&mut deps, 6000058);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{
+        to_address: "creator".to_string(),
+        amount: vec![Coin::new(6000058, "uusd")],
+    }));
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.aust_amount, Uint128::new(200000000 - 5454594));
+    assert_eq!(x.yield_claimed, Uint128::new(5454594));
+
+    //-----claims don't move the others' shares---------------
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+    assert_eq!(accrued(deps.as_ref(), "backer1"), Uint128::new(5000049));
+    execute(deps.as_mut(), mock_env(), mock_info("wefund", &[]), claim.clone()).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), claim).unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+
+    //-----milestones under the collected amount: the last release takes the rest of the principal-----
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_exchange_rate(Decimal256::one());
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut project_milestones, .. } = add_project {
+        *project_milestones = vec![mock_milestone(0, 100), mock_milestone(1, 50)];
+    }
+    setup_project(deps.as_mut(), add_project, &[("backer1", 100)]);
+    for &(rate, released) in &[("1", 100000000), ("1.1", 99999999)] {
+        deps.querier.with_exchange_rate(Decimal256::from_str(rate).unwrap());
+        let vote = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+            wallet: String::from("backer1"), vote: VoteOption::Yes };
+        execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), vote).unwrap();
+        let release = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), release).unwrap();
+        mock_redeem_reply(&mut deps, released);
+    }
+    let x = load_project(deps.as_ref(), 1);
+    assert_eq!(x.project_status, ProjectStatus::Done);
+    assert_eq!(x.released_amount, Uint128::new(199999999));
+    assert_eq!(x.aust_amount, Uint128::new(9090910)); //100000000 - 90909090 redeemed at 1.1
+
+    //-----only what the rate added stays, and it is split as yield-----------
+    assert_eq!(accrued(deps.as_ref(), "wefund"), Uint128::new(1999999)); //20% of 10000001 - 1 ust, rounded down
+}
+
+#[test]
+fn yield_strategies(){
+    //-----hold: no deposit, no redeem, shares are uusd-----------
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+    assert_eq!(load_project(deps.as_ref(), 1).yield_strategy, YieldStrategy::Anchor);
+
+    let msg = ExecuteMsg::SetProjectStrategy{ project_id: Uint128::new(1), strategy: YieldStrategy::Hold };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NotCorrectStatus{ .. })); //already approved
+
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: Some(String::from("wefund")),
+        anchor_market: Some("market".to_string()), aust_token: Some("aust".to_string()),
+        vesting_contract: None, community_manager: None, timelock_period: None, yield_split: None,
+        yield_strategy: Some(YieldStrategy::Hold), fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), mock_add_project()).unwrap();
+    let msg = ExecuteMsg::WefundApprove{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let msg = ExecuteMsg::AddCommunitymember{ wallet: String::from("community1") };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(load_project(deps.as_ref(), 1).yield_strategy, YieldStrategy::Hold);
+
+    let res = back_project(deps.as_mut(), "backer1", 100);
+    assert_eq!(res.messages.len(), 1); //only the wefund fee
+    let backers = load_backers(&deps.storage, Uint128::new(1), false).unwrap();
+    assert_eq!(backers[0].aust_amount.amount, Uint128::new(100000000));
+    back_project(deps.as_mut(), "community1", 100);
+
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{
+        to_address: "creator".to_string(),
+        amount: vec![Coin::new(100000000, "uusd")],
+    }));
+
+    //-----vault: deposit{} with reply, shares redeemed through the share token------
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut project_milestones, .. } = add_project {
+        *project_milestones = vec![mock_milestone(0, 200)];
+    }
+    let info = mock_info("admin", &[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: Some(String::from("wefund")),
+        anchor_market: Some("market".to_string()), aust_token: Some("aust".to_string()),
+        vesting_contract: None, community_manager: None, timelock_period: None, yield_split: None,
+        yield_strategy: None,
+        fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    execute(deps.as_mut(), mock_env(), info.clone(), add_project).unwrap();
+    let vault = YieldStrategy::Vault{ vault: Addr::unchecked("vault"), share_token: Addr::unchecked("vshare") };
+    let msg = ExecuteMsg::SetProjectStrategy{ project_id: Uint128::new(1), strategy: vault };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let msg = ExecuteMsg::WefundApprove{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let msg = ExecuteMsg::AddCommunitymember{ wallet: String::from("community1") };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = back_project(deps.as_mut(), "backer1", 100);
+    let deposit = res.messages.iter().find(|sub| sub.id == DEPOSIT_REPLY_ID).unwrap();
+    assert!(matches!(&deposit.msg, CosmosMsg::Wasm(WasmMsg::Execute{ contract_addr, .. }) if contract_addr == "vault"));
+    mock_deposit_reply(deps.as_mut(), Some(deposited(&res)));
+    back_project(deps.as_mut(), "community1", 100);
+    mock_deposit_reply(deps.as_mut(), None); //at 1.000001
+
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute{ contract_addr, msg, .. }) => {
+            assert_eq!(contract_addr, "vshare");
+            assert_eq!(from_binary::<Cw20ExecuteMsg>(msg).unwrap(), Cw20ExecuteMsg::Send{
+                contract: "vault".to_string(),
+                amount: Uint128::new(199999800), //200 ust at 1.000001
+                msg: to_binary(&VaultHook::Redeem{}).unwrap(),
+            });
+        }
+        msg => panic!("unexpected redeem {:?}", msg),
+    }
+}
+
+#[test]
+fn redemption_pays_what_arrived(){
+    let mut deps = mock_dependencies(&[Coin::new(7000000, "uusd")]); //unrelated ust already held
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_releasing_project(deps.as_mut());
+
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 1); //no payout before the ust is in
+    assert_eq!(res.messages[0].id, REDEEM_REPLY_ID);
+
+    //-----tax or a worse rate: pay what came in, keep the difference on the project------
+    let res = mock_redeem_reply(&mut deps, 99000000);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{
+        to_address: "creator".to_string(),
+        amount: vec![Coin::new(99000000, "uusd")],
+    }));
+    let expected = 99999900u128 * 1000001 / 1000000; //100 ust in aust, back at 1.000001
+    assert!(res.attributes.iter().any(|attr| attr.key == "shortfall"
+        && attr.value == (expected - 99000000).to_string()));
+    assert_eq!(load_project(deps.as_ref(), 1).redemption_shortfall, Uint128::new(expected - 99000000));
+
+    //-----nothing pending anymore--------------------------
+    let msg = Reply{ id: REDEEM_REPLY_ID, result: ContractResult::Ok(SubMsgExecutionResponse{
+        events: vec![], data: None
+    })};
+    assert!(reply(deps.as_mut(), mock_env(), msg).is_err());
+}
+
+#[test]
+fn exchange_rate_precision(){
+    //-----shares round down, principal rounds up, ust paid out rounds down----------
+    let rate = Decimal256::from_str("1.2").unwrap();
+    assert_eq!(ust_to_shares(Uint128::new(100), rate).unwrap(), Uint128::new(83));
+    assert_eq!(ust_to_shares_ceil(Uint128::new(100), rate).unwrap(), Uint128::new(84));
+    assert_eq!(shares_to_ust(Uint128::new(83), rate).unwrap(), Uint128::new(99));
+
+    for &rate in &["1", "3", "1.000001", "0.999999999999999999", "1.234567891234567891", "250000.5"] {
+        let rate = Decimal256::from_str(rate).unwrap();
+        for &ust in &[1u128, 999999, 100000000, 123456789012345] {
+            let ust = Uint128::new(ust);
+            let shares = ust_to_shares(ust, rate).unwrap();
+            let ceil = ust_to_shares_ceil(ust, rate).unwrap();
+            assert!(shares_to_ust(shares, rate).unwrap() <= ust);
+            assert!(shares_to_ust(ceil, rate).unwrap() >= ust);
+            assert!(ceil == shares || ceil == shares + Uint128::new(1));
+        }
+    }
+    assert!(ust_to_shares(Uint128::new(100), Decimal256::zero()).is_err());
+    assert!(shares_to_ust(Uint128::MAX, Decimal256::from_str("2").unwrap()).is_err());
+
+    //-----all 18 decimals count, the rest of the ust stays with the backers---------
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.000001").unwrap());
+    setup_releasing_project(deps.as_mut());
+    deps.querier.with_exchange_rate(Decimal256::from_str("1.234567891234567891").unwrap());
+
+    let msg = ExecuteMsg::SetMilestoneVote{ project_id: Uint128::new(1),
+        wallet: String::from("backer1"), vote: VoteOption::Yes };
+    execute(deps.as_mut(), mock_env(), mock_info("backer1", &[]), msg).unwrap();
+    let msg = ExecuteMsg::ReleaseMilestone{ project_id: Uint128::new(1) };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "exchange_rate"
+        && attr.value == "1.234567891234567891"));
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute{ msg, .. }) => match from_binary(msg).unwrap() {
+            Cw20ExecuteMsg::Send{ amount, .. } => assert_eq!(amount, Uint128::new(81000000)),
+            _ => panic!("expected a cw20 send"),
+        },
+        _ => panic!("expected a wasm execute"),
+    }
+    assert_eq!(load_project(deps.as_ref(), 1).aust_amount, Uint128::new(200000000 - 81000000));
+
+    let res = mock_redeem_reply(&mut deps, 99999999); //81 aust * 1.234567891234567891, rounded down
+    assert!(res.attributes.iter().any(|attr| attr.key == "shortfall" && attr.value == "0"));
+    assert_eq!(load_project(deps.as_ref(), 1).redemption_shortfall, Uint128::zero());
+}
+
+#[test]
+fn fee_schedule(){
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+
+    let back = |deps: DepsMut, amount: u128| {
+        let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer1"),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(),
+            referrer: None };
+        execute(deps, mock_env(), mock_info("backer1", &[Coin::new(amount, "uusd")]), msg)
+    };
+    let wefund_fee = |res: &Response| -> Vec<Coin> {
+        res.messages.iter().find_map(|sub| match &sub.msg {
+            CosmosMsg::Bank(BankMsg::Send{ to_address, amount }) if to_address == "wefund" => Some(amount.clone()),
+            _ => None,
+        }).unwrap()
+    };
+    let fee_event = |res: &Response, key: &str| -> String {
+        let event = res.events.iter().find(|event| event.ty == "fee").unwrap();
+        event.attributes.iter().find(|attr| attr.key == key).unwrap().value.clone()
+    };
+
+    //-----default terms: 6 ust minimum, 5 ust below 100 ust, 5/105 above, 4 ust kept----------
+    let err = back(deps.as_mut(), 5000000).unwrap_err();
+    assert!(matches!(err, ContractError::NeedCoin{}));
+    let res = back(deps.as_mut(), 10000000).unwrap();
+    assert_eq!(deposited(&res), 5000000);
+    assert_eq!(wefund_fee(&res), vec![Coin::new(1000000, "uusd")]);
+    assert_eq!(fee_event(&res, "tier"), "0");
+    assert_eq!(fee_event(&res, "contract_fee"), "4000000");
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----owner only, tiers ascending and never taking the whole backing-------------
+    let tier = |min_amount: u128, percent: u128, flat_fee: u128, contract_fee: u128| FeeTier{
+        min_amount: Uint128::new(min_amount), percent: Uint128::new(percent),
+        flat_fee: Uint128::new(flat_fee), contract_fee: Uint128::new(contract_fee),
+    };
+    let campaign = FeeSchedule{ tiers: vec![tier(0, 2, 0, 0)], min_contribution: Uint128::new(1000000),
+        recipients: Vec::new() };
+    let msg = ExecuteMsg::SetFeeSchedule{ fee_schedule: campaign.clone() };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("manager", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    for tiers in vec![vec![], vec![tier(0, 0, 0, 0), tier(0, 5, 0, 0)], vec![tier(0, 0, 1000000, 0)],
+        vec![tier(0, 5, 0, 100000)]] {
+        let msg = ExecuteMsg::SetFeeSchedule{ fee_schedule: FeeSchedule{ tiers, min_contribution: Uint128::new(1000000),
+            recipients: Vec::new() } };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeSchedule{}));
+    }
+
+    //-----a campaign override for the project, then back to the config terms--------
+    let schedule = |deps: Deps, project_id: Option<u128>| -> FeeSchedule {
+        let msg = QueryMsg::GetFeeSchedule{ project_id: project_id.map(Uint128::new) };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    let msg = ExecuteMsg::SetProjectFeeSchedule{ project_id: Uint128::new(1), fee_schedule: Some(campaign.clone()) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(schedule(deps.as_ref(), Some(1)), campaign);
+    assert_eq!(schedule(deps.as_ref(), None), FeeSchedule::default());
+
+    let res = back(deps.as_mut(), 51000000).unwrap();
+    assert_eq!(deposited(&res), 50000000);
+    assert_eq!(wefund_fee(&res), vec![Coin::new(1000000, "uusd")]);
+    assert_eq!(fee_event(&res, "contract_fee"), "0");
+    mock_deposit_reply(deps.as_mut(), None);
+
+    let msg = ExecuteMsg::SetProjectFeeSchedule{ project_id: Uint128::new(1), fee_schedule: None };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert_eq!(schedule(deps.as_ref(), Some(1)), FeeSchedule::default());
+    let res = back(deps.as_mut(), 100000000).unwrap();
+    assert_eq!(deposited(&res), 95238095); //100 of every 105
+    assert_eq!(wefund_fee(&res), vec![Coin::new(761905, "uusd")]);
+    assert_eq!(fee_event(&res, "tier"), "1");
+    assert_eq!(fee_event(&res, "backed"), "95238095");
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----TransferAllCoins sends only the contract fees kept, once----------------
+    let transfer = |deps: DepsMut, sender: &str| {
+        let msg = ExecuteMsg::TransferAllCoins{ wallet: String::from("treasury") };
+        execute(deps, mock_env(), mock_info(sender, &[]), msg)
+    };
+    let err = transfer(deps.as_mut(), "manager").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let res = transfer(deps.as_mut(), "admin").unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send{ to_address: String::from("treasury"),
+        amount: vec![Coin::new(8000000, "uusd")] }));
+    let err = transfer(deps.as_mut(), "admin").unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+}
+
+#[test]
+fn fee_recipients(){
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+
+    let back = |deps: DepsMut, amount: u128| -> Response {
+        let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer1"),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(),
+            referrer: None };
+        execute(deps, mock_env(), mock_info("backer1", &[Coin::new(amount, "uusd")]), msg).unwrap()
+    };
+    let sent = |res: &Response| -> Vec<(String, u128)> {
+        res.messages.iter().filter_map(|sub| match &sub.msg {
+            CosmosMsg::Bank(BankMsg::Send{ to_address, amount }) => Some((to_address.clone(), amount[0].amount.u128())),
+            _ => None,
+        }).collect()
+    };
+    let accrued = |deps: Deps, wallet: &str| -> Uint128 {
+        let msg = QueryMsg::GetAccruedFees{ wallet: String::from(wallet) };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    let recipient = |address: &str, weight: u128| FeeRecipient{
+        address: Addr::unchecked(address), weight: Uint128::new(weight)
+    };
+
+    //-----weights must add up to 100%--------------------------
+    let mut schedule = FeeSchedule{
+        recipients: vec![recipient("treasury", 50), recipient("insurance", 40)],
+        ..FeeSchedule::default()
+    };
+    let msg = ExecuteMsg::SetFeeSchedule{ fee_schedule: schedule.clone() };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFeeWeights{ total } if total == Uint128::new(90)));
+
+    schedule.recipients = vec![recipient("treasury", 50), recipient("insurance", 33), recipient("curator", 17)];
+    let msg = ExecuteMsg::SetFeeSchedule{ fee_schedule: schedule.clone() };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let res = back(deps.as_mut(), 10000000); //1 ust to split
+    assert_eq!(sent(&res), vec![("treasury".to_string(), 500000), ("insurance".to_string(), 330000),
+        ("curator".to_string(), 170000)]);
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----small shares accrue, the rounding remainder goes into the next fee------------
+    let campaign = FeeSchedule{
+        tiers: vec![FeeTier{ min_amount: Uint128::zero(), percent: Uint128::new(2),
+            flat_fee: Uint128::zero(), contract_fee: Uint128::zero() }],
+        min_contribution: Uint128::new(1000000),
+        recipients: schedule.recipients,
+    };
+    let msg = ExecuteMsg::SetProjectFeeSchedule{ project_id: Uint128::new(1), fee_schedule: Some(campaign) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let res = back(deps.as_mut(), 1020001); //20001 fee
+    assert_eq!(sent(&res), vec![("treasury".to_string(), 10000)]);
+    assert_eq!(accrued(deps.as_ref(), "insurance"), Uint128::new(6600));
+    assert_eq!(accrued(deps.as_ref(), "curator"), Uint128::new(3400));
+    mock_deposit_reply(deps.as_mut(), None);
+
+    let res = back(deps.as_mut(), 1020000); //20000 fee, plus 1 carried
+    assert_eq!(sent(&res), vec![("treasury".to_string(), 10000), ("insurance".to_string(), 13200)]);
+    assert_eq!(accrued(deps.as_ref(), "insurance"), Uint128::zero());
+    assert_eq!(accrued(deps.as_ref(), "curator"), Uint128::new(6800));
+
+    //-----what is accrued can be claimed at any size-----------------
+    let claim = ExecuteMsg::ClaimFees{};
+    let res = execute(deps.as_mut(), mock_env(), mock_info("curator", &[]), claim.clone()).unwrap();
+    assert_eq!(sent(&res), vec![("curator".to_string(), 6800)]);
+    assert_eq!(accrued(deps.as_ref(), "curator"), Uint128::zero());
+    let err = execute(deps.as_mut(), mock_env(), mock_info("curator", &[]), claim).unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim{}));
+}
+
+#[test]
+fn referral_program(){
+    let mut deps = mock_dependencies(&[]);
+    setup_fundraising_project(deps.as_mut(), mock_add_project());
+
+    let back = |deps: DepsMut, wallet: &str, amount: u128, referrer: &str| {
+        let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from(wallet),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(),
+            referrer: Some(String::from(referrer)) };
+        execute(deps, mock_env(), mock_info(wallet, &[Coin::new(amount, "uusd")]), msg)
+    };
+    let sent = |res: &Response| -> Vec<(String, u128)> {
+        res.messages.iter().filter_map(|sub| match &sub.msg {
+            CosmosMsg::Bank(BankMsg::Send{ to_address, amount }) => Some((to_address.clone(), amount[0].amount.u128())),
+            _ => None,
+        }).collect()
+    };
+    let stats = |deps: Deps, referrer: &str| -> ReferralStatsResponse {
+        let msg = QueryMsg::GetReferralStats{ referrer: String::from(referrer) };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+
+    let set_share = |deps: DepsMut, share: u128| {
+        let msg = ExecuteMsg::SetConfig{ wefund: None, community_manager: None, yield_split: None,
+            referral_share: Some(Uint128::new(share)) };
+        execute(deps, mock_env(), mock_info("admin", &[]), msg)
+    };
+    let err = set_share(deps.as_mut(), 101).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidReferralShare{ .. }));
+    set_share(deps.as_mut(), 25).unwrap();
+
+    //-----no referring yourself or the creator's own project---------------
+    let err = back(deps.as_mut(), "backer1", 10000000, "backer1").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidReferrer{}));
+    let err = back(deps.as_mut(), "backer1", 10000000, "creator").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidReferrer{}));
+
+    //-----a quarter of wefund's fee goes to the referrer----------------
+    let res = back(deps.as_mut(), "backer1", 10000000, "affiliate").unwrap();
+    assert_eq!(sent(&res), vec![("affiliate".to_string(), 250000), ("wefund".to_string(), 750000)]);
+    mock_deposit_reply(deps.as_mut(), None);
+    let res = back(deps.as_mut(), "backer2", 100000000, "affiliate").unwrap();
+    assert_eq!(sent(&res), vec![("affiliate".to_string(), 190476), ("wefund".to_string(), 571429)]);
+    assert!(res.events[0].attributes.iter().any(|attr| attr.key == "referral_fee" && attr.value == "190476"));
+    mock_deposit_reply(deps.as_mut(), None);
+
+    let expected = ReferralStats{
+        backings: Uint128::new(2),
+        backed: Uint128::new(5000000 + 95238095),
+        earned: Uint128::new(250000 + 190476),
+    };
+    let res = stats(deps.as_ref(), "affiliate");
+    assert_eq!(res.total, expected);
+    assert_eq!(res.projects, vec![ProjectReferrals{ project_id: Uint128::new(1), stats: expected }]);
+    assert_eq!(stats(deps.as_ref(), "nobody").total, ReferralStats::default());
+}
+
+#[test]
+fn token_allocation(){
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg{
+        admin: Some(String::from("admin")), wefund: Some(String::from("wefund")),
+        anchor_market: Some("market".to_string()), aust_token: Some("aust".to_string()),
+        vesting_contract: Some("vesting".to_string()), community_manager: None, timelock_period: None,
+        yield_split: None, yield_strategy: None, fee_schedule: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let stage = |title: &str, price: u128, amount: u128| VestingParameter{
+        stage_title: title.to_string(), stage_price: Uint128::new(price), stage_amount: Uint128::new(amount),
+        stage_soon: Uint128::zero(), stage_after: Uint128::zero(), stage_period: Uint128::zero(), stage_end: None,
+    };
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut vesting, ref mut token_addr, .. } = add_project {
+        *vesting = vec![stage("seed", 50000, 1000), stage("presale", 100000, 5000)];
+        *token_addr = "token".to_string();
+    }
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), add_project).unwrap();
+    let msg = ExecuteMsg::WefundApprove{ project_id: Uint128::new(1) };
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let back = |deps: DepsMut, amount: u128| {
+        let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer1"),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(), referrer: None };
+        execute(deps, mock_env(), mock_info("backer1", &[Coin::new(amount, "uusd")]), msg)
+    };
+    let add_users = |res: &Response| -> Vec<(u128, u128)> {
+        res.messages.iter().filter_map(|sub| match &sub.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute{ contract_addr, msg, .. }) if contract_addr == "vesting" =>
+                match from_binary(msg).unwrap() {
+                    VestingMsg::AddUser{ stage, amount, .. } => Some((stage.u128(), amount.u128())),
+                    _ => None,
+                },
+            _ => None,
+        }).collect()
+    };
+
+    //-----only uusd buys tokens, alone---------------------------
+    let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer1"),
+        otherchain: "".to_string(), otherchain_wallet: "".to_string(), referrer: None };
+    for funds in vec![vec![Coin::new(21000000, "ukrw")],
+        vec![Coin::new(21000000, "uusd"), Coin::new(1, "uluna")]] {
+        let err = execute(deps.as_mut(), mock_env(), mock_info("backer1", &funds), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongDenom{}));
+    }
+
+    //-----16 ust backed at 0.05 ust, in the token's 6 decimals-----------
+    let res = back(deps.as_mut(), 21000000).unwrap();
+    assert_eq!(add_users(&res), vec![(0, 320000000)]);
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----the seed stage has 680 tokens left, the rest goes at the presale price------
+    let res = back(deps.as_mut(), 52500000).unwrap(); //47.5 ust backed
+    assert_eq!(add_users(&res), vec![(0, 680000000), (1, 135000000)]);
+    mock_deposit_reply(deps.as_mut(), None);
+    assert_eq!(load_project(deps.as_ref(), 1).fundraising_stage, Uint128::new(1));
+
+    let res = back(deps.as_mut(), 25000000).unwrap();
+    assert_eq!(add_users(&res), vec![(1, 200000000)]);
+    mock_deposit_reply(deps.as_mut(), None);
+
+    let msg = QueryMsg::GetStageSales{ project_id: Uint128::new(1) };
+    let sales: Vec<StageSale> = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(sales.iter().map(|sale| sale.sold.u128()).collect::<Vec<_>>(), vec![1000000000, 335000000]);
+
+    //-----tokens vest to the backer wallet, not to whoever sent the backing------
+    let mut msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("x"),
+        otherchain: "".to_string(), otherchain_wallet: "".to_string(), referrer: None };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[Coin::new(11000000, "uusd")]), msg.clone())
+        .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidAddress{}));
+    if let ExecuteMsg::Back2Project{ ref mut backer_wallet, .. } = msg {
+        *backer_wallet = String::from("backer2");
+    }
+    let res = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[Coin::new(11000000, "uusd")]), msg).unwrap();
+    let wallets: Vec<Addr> = res.messages.iter().filter_map(|sub| match &sub.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute{ contract_addr, msg, .. }) if contract_addr == "vesting" =>
+            match from_binary(msg).unwrap() {
+                VestingMsg::AddUser{ wallet, .. } => Some(wallet),
+                _ => None,
+            },
+        _ => None,
+    }).collect();
+    assert_eq!(wallets, vec![Addr::unchecked("backer2")]);
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----only the owner moves the stage by hand, never back or past the last one------
+    let set_stage = |stage: u128| ExecuteMsg::SetFundraisingStage{ project_id: Uint128::new(1),
+        stage: Uint128::new(stage) };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), set_stage(1)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized{}));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set_stage(2)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidStage{ .. }));
+    for &stage in &[0, 1] {
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set_stage(stage)).unwrap_err();
+        assert!(matches!(err, ContractError::StageNotAhead{ .. }));
+    }
+}
+
+#[test]
+fn fundraising_stage_progression(){
+    let mut deps = mock_dependencies(&[]);
+    let seed_end = mock_env().block.time.seconds() + 100;
+    let stage = |price: u128, amount: u128, stage_end: Option<u64>| VestingParameter{
+        stage_title: "stage".to_string(), stage_price: Uint128::new(price), stage_amount: Uint128::new(amount),
+        stage_soon: Uint128::zero(), stage_after: Uint128::zero(), stage_period: Uint128::zero(),
+        stage_end: stage_end.map(Uint128::from),
+    };
+    let mut add_project = mock_add_project();
+    if let ExecuteMsg::AddProject{ ref mut vesting, .. } = add_project {
+        *vesting = vec![stage(1000000, 10, Some(seed_end)), stage(2000000, 5, None)];
+    }
+    setup_fundraising_project(deps.as_mut(), add_project);
+
+    let back = |deps: DepsMut, env: Env, amount: u128| {
+        let msg = ExecuteMsg::Back2Project{ project_id: Uint128::new(1), backer_wallet: String::from("backer1"),
+            otherchain: "".to_string(), otherchain_wallet: "".to_string(), referrer: None };
+        execute(deps, env, mock_info("backer1", &[Coin::new(amount, "uusd")]), msg)
+    };
+    let current = |deps: Deps, env: Env| -> FundraisingStageResponse {
+        let msg = QueryMsg::GetFundraisingStage{ project_id: Uint128::new(1) };
+        from_binary(&query(deps, env, msg).unwrap()).unwrap()
+    };
+
+    let res = current(deps.as_ref(), mock_env());
+    assert_eq!(res, FundraisingStageResponse{ open: true, stage: Uint128::zero(), stage_price: Uint128::new(1000000),
+        remaining: Uint128::new(10), stage_end: Some(Uint128::from(seed_end)) });
+    back(deps.as_mut(), mock_env(), 10000000).unwrap(); //5 ust backed, 5 tokens
+    mock_deposit_reply(deps.as_mut(), None);
+    assert_eq!(current(deps.as_ref(), mock_env()).remaining, Uint128::new(5));
+
+    //-----the seed window ends with tokens left, the sale moves on---------------
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(200);
+    let res = current(deps.as_ref(), later.clone());
+    assert_eq!((res.stage, res.stage_price, res.remaining), (Uint128::new(1), Uint128::new(2000000), Uint128::new(5)));
+    back(deps.as_mut(), later.clone(), 11000000).unwrap(); //6 ust, 3 tokens
+    mock_deposit_reply(deps.as_mut(), None);
+
+    //-----more than the last stage holds is refused, the exact rest is not----------
+    let err = back(deps.as_mut(), later.clone(), 11000000).unwrap_err();
+    assert!(matches!(err, ContractError::StageSoldOut{}));
+    back(deps.as_mut(), later.clone(), 9000000).unwrap(); //4 ust, the last 2 tokens
+    mock_deposit_reply(deps.as_mut(), None);
+    assert!(!current(deps.as_ref(), later).open);
+
+    let msg = QueryMsg::GetStageSales{ project_id: Uint128::new(1) };
+    let sales: Vec<StageSale> = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(sales.iter().map(|sale| sale.sold.u128()).collect::<Vec<_>>(), vec![5, 5]);
+}